@@ -0,0 +1,717 @@
+// File: cpi_detee/src/fleet.rs
+//! Actions working on the whole fleet of workers: batches, manifests, costs, expiries and reports
+
+use super::*;
+
+// Fleet-wide totals and averages of per-worker metrics; workers without a cpu_percent sample
+// were skipped and only counted
+fn summarize_utilization(reports: &[Value]) -> Value {
+    let sampled: Vec<&Value> = reports.iter().filter(|r| r["cpu_percent"].is_number()).collect();
+    let sum = |field: &str| sampled.iter().filter_map(|r| r[field].as_f64()).sum::<f64>();
+    let round = |value: f64| (value * 10.0).round() / 10.0;
+    
+    let memory_total_mb = sum("memory_total_mb");
+    let memory_used_mb = sum("memory_used_mb");
+    let average = |field: &str| if sampled.is_empty() { Value::Null } else { json!(round(sum(field) / sampled.len() as f64)) };
+    
+    json!({
+        "total": reports.len(),
+        "sampled": sampled.len(),
+        "skipped": reports.len() - sampled.len(),
+        "memory_total_mb": memory_total_mb as u64,
+        "memory_used_mb": memory_used_mb as u64,
+        "memory_percent": if memory_total_mb > 0.0 { json!(round(memory_used_mb * 100.0 / memory_total_mb)) } else { Value::Null },
+        "average_cpu_percent": average("cpu_percent"),
+        "average_memory_percent": average("memory_percent")
+    })
+}
+
+// Workers of an exported inventory by UUID, accepting list_workers' { "workers": [...] }
+// result or a bare array
+fn inventory_workers<'a>(inventory: &'a Value, name: &str) -> Result<BTreeMap<&'a str, &'a Value>, String> {
+    let list = match inventory {
+        Value::Object(map) => map.get("workers").and_then(|w| w.as_array()),
+        Value::Array(list) => Some(list),
+        _ => None,
+    }.ok_or_else(|| format!("Parameter '{}' must be an inventory with a 'workers' array", name))?;
+    
+    list.iter()
+        .map(|worker| match worker["uuid"].as_str() {
+            Some(uuid) => Ok((uuid, worker)),
+            None => Err(format!("Parameter '{}' has a worker without a 'uuid'", name)),
+        })
+        .collect()
+}
+
+// Compare two inventories by UUID. A VM's time left only goes down on its own, so more
+// time left in the later inventory means it was extended.
+pub(crate) fn fleet_diff(before: &Value, after: &Value) -> Result<Value, String> {
+    let before = inventory_workers(before, "before")?;
+    let after = inventory_workers(after, "after")?;
+    
+    let added: Vec<&Value> = after.iter().filter(|(uuid, _)| !before.contains_key(*uuid)).map(|(_, w)| *w).collect();
+    let removed: Vec<&Value> = before.iter().filter(|(uuid, _)| !after.contains_key(*uuid)).map(|(_, w)| *w).collect();
+    
+    let lp_per_hour = |worker: &Value| worker["lp_per_hour"].as_f64().unwrap_or(0.0);
+    let added_lp: f64 = added.iter().map(|w| lp_per_hour(w)).sum();
+    let removed_lp: f64 = removed.iter().map(|w| lp_per_hour(w)).sum();
+    let mut resized_lp = 0.0;
+    
+    let mut resized = Vec::new();
+    let mut renewed = Vec::new();
+    for (uuid, old) in &before {
+        let Some(new) = after.get(uuid) else { continue };
+        
+        let changes: serde_json::Map<String, Value> = ["cores", "memory_mb", "disk_gb"].iter()
+            .filter(|field| old[**field] != new[**field])
+            .map(|field| (field.to_string(), json!({ "before": old[*field], "after": new[*field] })))
+            .collect();
+        if !changes.is_empty() {
+            resized_lp += lp_per_hour(new) - lp_per_hour(old);
+            resized.push(json!({ "uuid": uuid, "hostname": new["hostname"], "changes": changes }));
+        }
+        
+        let secs_left = |worker: &Value| worker["time_left"].as_str().and_then(parse_duration_secs);
+        if let (Some(old_secs), Some(new_secs)) = (secs_left(old), secs_left(new)) {
+            if new_secs > old_secs {
+                renewed.push(json!({
+                    "uuid": uuid,
+                    "hostname": new["hostname"],
+                    "time_left_before": old["time_left"],
+                    "time_left_after": new["time_left"]
+                }));
+            }
+        }
+    }
+    
+    Ok(json!({
+        "success": true,
+        "changed": !(added.is_empty() && removed.is_empty() && resized.is_empty() && renewed.is_empty()),
+        "added": added,
+        "removed": removed,
+        "resized": resized,
+        "renewed": renewed,
+        "cost_impact": cost_impact(added_lp + resized_lp.max(0.0), removed_lp + (-resized_lp).max(0.0))
+    }))
+}
+
+// Hourly LP cost change of added and removed capacity, projected per day and month
+fn cost_impact(added_lp_per_hour: f64, removed_lp_per_hour: f64) -> Value {
+    let net = added_lp_per_hour - removed_lp_per_hour;
+    json!({
+        "added_lp_per_hour": added_lp_per_hour,
+        "removed_lp_per_hour": removed_lp_per_hour,
+        "net_lp_per_hour": net,
+        "net_lp_per_day": net * HOURS_PER_DAY,
+        "net_lp_per_month": net * HOURS_PER_MONTH
+    })
+}
+
+// Quote a CSV field when it contains a separator, quote or line break (RFC 4180)
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+impl DeeTeeExtension {
+    pub(crate) fn auto_renew(&self, threshold_hours: i64, extend_hours: i64) -> ActionResult {
+        if extend_hours <= 0 {
+            return Err("Parameter 'extend_hours' must be a positive integer".to_string());
+        }
+        if threshold_hours <= 0 {
+            return Err("Parameter 'threshold_hours' must be a positive integer".to_string());
+        }
+        let threshold_secs = threshold_hours.checked_mul(3600)
+            .ok_or_else(|| "Parameter 'threshold_hours' is too large".to_string())?;
+        
+        let mut renewed = Vec::new();
+        let mut skipped = Vec::new();
+        let mut failed = Vec::new();
+        
+        for worker in self.fetch_workers()? {
+            let seconds = match parse_duration_secs(&worker.time_left) {
+                Some(seconds) => seconds,
+                None => {
+                    skipped.push(json!({
+                        "uuid": worker.uuid,
+                        "reason": format!("Unparseable time left '{}'", worker.time_left)
+                    }));
+                    continue;
+                }
+            };
+            
+            if seconds >= threshold_secs {
+                skipped.push(json!({
+                    "uuid": worker.uuid,
+                    "seconds_remaining": seconds
+                }));
+                continue;
+            }
+            
+            let hours_param = format!("--hours {}", extend_hours);
+            match self.update_worker(worker.uuid.clone(), String::new(), String::new(), hours_param) {
+                Ok(result) => renewed.push(json!({
+                    "uuid": worker.uuid,
+                    "hostname": worker.hostname,
+                    "seconds_remaining": seconds,
+                    "result": result
+                })),
+                Err(e) => failed.push(json!({
+                    "uuid": worker.uuid,
+                    "error": e
+                })),
+            }
+        }
+        
+        Ok(json!({
+            "success": failed.is_empty(),
+            "renewed": renewed,
+            "skipped": skipped,
+            "failed": failed
+        }))
+    }
+    
+    pub(crate) fn batch_create_workers(&self, specs: Vec<WorkerSpec>, concurrency: usize) -> ActionResult {
+        let results = run_bounded(&specs, concurrency, |spec| {
+            match self.create_worker(spec, false) {
+                Ok(result) => json!({ "success": true, "spec": spec, "result": result }),
+                Err(e) => json!({ "success": false, "spec": spec, "error": e }),
+            }
+        });
+        
+        let failed = results.iter().filter(|r| r["success"] != json!(true)).count();
+        
+        Ok(json!({
+            "success": failed == 0,
+            "created": results.len() - failed,
+            "failed": failed,
+            "results": results
+        }))
+    }
+    
+    pub(crate) fn batch_delete_workers(&self, worker_ids: Vec<String>, concurrency: usize) -> ActionResult {
+        let results = run_bounded(&worker_ids, concurrency, |worker_id| {
+            match self.delete_worker(worker_id.clone()) {
+                Ok(_) => json!({ "success": true, "worker_id": worker_id }),
+                Err(e) => json!({ "success": false, "worker_id": worker_id, "error": e }),
+            }
+        });
+        
+        let failed = results.iter().filter(|r| r["success"] != json!(true)).count();
+        
+        Ok(json!({
+            "success": failed == 0,
+            "deleted": results.len() - failed,
+            "failed": failed,
+            "results": results
+        }))
+    }
+    
+    // Reconcile the fleet with a manifest, matching workers by hostname: missing ones are
+    // created, matching ones left alone, and extras are only deleted when pruning
+    pub(crate) fn apply_manifest(&self, specs: Vec<WorkerSpec>, prune: bool, dry_run: bool, concurrency: usize) -> ActionResult {
+        let mut wanted = HashSet::new();
+        for spec in &specs {
+            let hostname = spec.hostname.as_deref()
+                .ok_or_else(|| "Each entry in 'workers' must have a 'hostname'".to_string())?;
+            if !wanted.insert(hostname) {
+                return Err(format!("Parameter 'workers' lists the hostname '{}' more than once", hostname));
+            }
+        }
+        
+        let existing = self.fetch_workers()?;
+        let existing_names: HashSet<&str> = existing.iter().map(|w| w.hostname.as_str()).collect();
+        
+        let missing: Vec<WorkerSpec> = specs.iter()
+            .filter(|spec| !existing_names.contains(spec.hostname.as_deref().unwrap_or_default()))
+            .cloned()
+            .collect();
+        let unchanged: Vec<Value> = existing.iter()
+            .filter(|w| wanted.contains(w.hostname.as_str()))
+            .map(|w| json!({ "uuid": w.uuid, "hostname": w.hostname }))
+            .collect();
+        let extras: Vec<&WorkerInfo> = existing.iter()
+            .filter(|w| !wanted.contains(w.hostname.as_str()))
+            .collect();
+        
+        if dry_run {
+            // The price of a new worker isn't known before its deploy, so estimate it from
+            // running workers with the same resources
+            let estimate = |spec: &WorkerSpec| {
+                let peers: Vec<f64> = existing.iter()
+                    .filter(|w| (w.cores, w.memory_mb, w.disk_gb) == (spec.vcpus, spec.memory_mb, spec.disk_gb))
+                    .map(|w| w.lp_per_hour)
+                    .collect();
+                (!peers.is_empty()).then(|| peers.iter().sum::<f64>() / peers.len() as f64)
+            };
+            let estimates: Vec<Option<f64>> = missing.iter().map(estimate).collect();
+            let freed: f64 = if prune { extras.iter().map(|w| w.lp_per_hour).sum() } else { 0.0 };
+            
+            let mut impact = cost_impact(estimates.iter().flatten().sum(), freed);
+            impact["unestimated"] = json!(estimates.iter().filter(|e| e.is_none()).count());
+            return Ok(json!({
+                "success": true,
+                "dry_run": true,
+                "create": missing.iter().zip(&estimates)
+                    .map(|(spec, lp)| json!({ "spec": spec, "estimated_lp_per_hour": lp }))
+                    .collect::<Vec<_>>(),
+                "unchanged": unchanged,
+                "extras": extras.iter().map(|w| json!({ "uuid": w.uuid, "hostname": w.hostname, "lp_per_hour": w.lp_per_hour })).collect::<Vec<_>>(),
+                "pruned": prune,
+                "cost_impact": impact
+            }));
+        }
+        
+        let created = self.batch_create_workers(missing, concurrency)?;
+        let deleted = if prune {
+            let extra_ids = extras.iter().map(|w| w.uuid.clone()).collect();
+            Some(self.batch_delete_workers(extra_ids, concurrency)?)
+        } else {
+            None
+        };
+        
+        let success = created["success"] == json!(true)
+            && deleted.as_ref().map(|d| d["success"] == json!(true)).unwrap_or(true);
+        Ok(json!({
+            "success": success,
+            "created": created["results"],
+            "unchanged": unchanged,
+            "extras": extras.iter().map(|w| json!({ "uuid": w.uuid, "hostname": w.hostname })).collect::<Vec<_>>(),
+            "pruned": prune,
+            "deleted": deleted.map(|d| d["results"].clone())
+        }))
+    }
+    
+    pub(crate) fn extend_all_workers(&self, additional_hours: i64) -> ActionResult {
+        if additional_hours <= 0 {
+            return Err("Parameter 'additional_hours' must be a positive integer".to_string());
+        }
+        
+        let hours_param = format!("--hours {}", additional_hours);
+        let results: Vec<Value> = self.fetch_workers()?
+            .into_iter()
+            .map(|worker| match self.update_worker(worker.uuid.clone(), String::new(), String::new(), hours_param.clone()) {
+                Ok(result) => json!({ "success": true, "worker_id": worker.uuid, "result": result }),
+                Err(e) => json!({ "success": false, "worker_id": worker.uuid, "error": e }),
+            })
+            .collect();
+        
+        let failed = results.iter().filter(|r| r["success"] != json!(true)).count();
+        
+        Ok(json!({
+            "success": failed == 0,
+            "extended": results.len() - failed,
+            "failed": failed,
+            "results": results
+        }))
+    }
+    
+    // Workers with a readable hourly rate, cheapest first (ties broken by UUID), and the rest
+    fn rank_by_cost(&self) -> Result<(Vec<WorkerInfo>, Vec<WorkerInfo>), String> {
+        let (mut priced, unpriced): (Vec<WorkerInfo>, Vec<WorkerInfo>) = self.fetch_workers()?
+            .into_iter()
+            .partition(|worker| !worker.unreadable("lp_per_hour") && worker.lp_per_hour.is_finite());
+        priced.sort_by(|a, b| a.lp_per_hour.total_cmp(&b.lp_per_hour).then_with(|| a.uuid.cmp(&b.uuid)));
+        Ok((priced, unpriced))
+    }
+    
+    pub(crate) fn worker_cost_ranking(&self) -> ActionResult {
+        let (ranked, unpriced) = self.rank_by_cost()?;
+        let warnings: Vec<String> = unpriced.iter()
+            .map(|worker| format!("Excluded worker {} with an unreadable hourly rate", worker.uuid))
+            .collect();
+        
+        let cheapest = ranked.first().map(|w| w.uuid.clone());
+        let most_expensive = ranked.last().map(|w| w.uuid.clone());
+        let ranking: Vec<Value> = ranked.iter().enumerate()
+            .map(|(i, worker)| json!({
+                "rank": i + 1,
+                "uuid": worker.uuid,
+                "hostname": worker.hostname,
+                "lp_per_hour": worker.lp_per_hour,
+                "lp_per_day": worker.lp_per_day,
+                "lp_per_month": worker.lp_per_month
+            }))
+            .collect();
+        
+        Ok(json!({
+            "success": true,
+            "ranking": ranking,
+            "cheapest": cheapest,
+            "most_expensive": most_expensive,
+            "warnings": warnings
+        }))
+    }
+    
+    pub(crate) fn cost_dashboard(&self) -> ActionResult {
+        let (ranked, unpriced) = self.rank_by_cost()?;
+        
+        let lp_per_hour: f64 = ranked.iter().map(|w| w.lp_per_hour).sum();
+        let workers: Vec<Value> = ranked.iter().rev()
+            .map(|worker| json!({
+                "uuid": worker.uuid,
+                "hostname": worker.hostname,
+                "city": worker.city,
+                "lp_per_hour": worker.lp_per_hour,
+                "lp_per_day": worker.lp_per_day,
+                "lp_per_month": worker.lp_per_month,
+                "share_percent": if lp_per_hour > 0.0 { (worker.lp_per_hour * 1000.0 / lp_per_hour).round() / 10.0 } else { 0.0 }
+            }))
+            .collect();
+        let warnings: Vec<String> = unpriced.iter()
+            .map(|worker| format!("Worker {} has an unreadable hourly rate and is not included in the totals", worker.uuid))
+            .collect();
+        
+        Ok(json!({
+            "success": true,
+            "workers": workers,
+            "unpriced": unpriced.iter().map(|w| json!({ "uuid": w.uuid, "hostname": w.hostname })).collect::<Vec<_>>(),
+            "total": {
+                "worker_count": ranked.len(),
+                "lp_per_hour": lp_per_hour,
+                "lp_per_day": ranked.iter().map(|w| w.lp_per_day).sum::<f64>(),
+                "lp_per_month": ranked.iter().map(|w| w.lp_per_month).sum::<f64>()
+            },
+            "warnings": warnings
+        }))
+    }
+    
+    pub(crate) fn expiry_timeline(&self) -> ActionResult {
+        let now = self.unix_now();
+        let mut timeline = Vec::new();
+        let mut warnings = Vec::new();
+        
+        for worker in self.fetch_workers()? {
+            match parse_duration_secs(&worker.time_left) {
+                Some(seconds) => timeline.push((now + seconds.max(0), worker)),
+                None => warnings.push(format!(
+                    "Excluded worker {} with unparseable time left '{}'",
+                    worker.uuid, worker.time_left
+                )),
+            }
+        }
+        
+        timeline.sort_by(|(a, wa), (b, wb)| a.cmp(b).then_with(|| wa.uuid.cmp(&wb.uuid)));
+        
+        let entries: Vec<Value> = timeline.iter()
+            .map(|(expires_at, worker)| json!({
+                "uuid": worker.uuid,
+                "hostname": worker.hostname,
+                "expires_at": expires_at,
+                "seconds_remaining": expires_at - now
+            }))
+            .collect();
+        
+        Ok(json!({
+            "success": true,
+            "now": now,
+            "nearest_expiry": entries.first().cloned(),
+            "timeline": entries,
+            "warnings": warnings
+        }))
+    }
+    
+    pub(crate) fn fleet_report(&self) -> ActionResult {
+        let workers = self.fetch_workers()?;
+        
+        let lp_per_hour: f64 = workers.iter().map(|w| w.lp_per_hour).sum();
+        let mut by_city: BTreeMap<&str, usize> = BTreeMap::new();
+        for worker in &workers {
+            *by_city.entry(worker.city.as_str()).or_default() += 1;
+        }
+        
+        let remaining: Vec<(&WorkerInfo, i64)> = workers.iter()
+            .filter_map(|w| parse_duration_secs(&w.time_left).map(|secs| (w, secs.max(0))))
+            .collect();
+        let average_secs_remaining = if remaining.is_empty() {
+            None
+        } else {
+            Some(remaining.iter().map(|(_, secs)| secs).sum::<i64>() / remaining.len() as i64)
+        };
+        let soonest_expiry = remaining.iter()
+            .min_by_key(|(w, secs)| (*secs, w.uuid.as_str()))
+            .map(|(w, secs)| json!({
+                "uuid": w.uuid,
+                "hostname": w.hostname,
+                "seconds_remaining": secs
+            }));
+        
+        Ok(json!({
+            "success": true,
+            "worker_count": workers.len(),
+            "lp_per_hour": lp_per_hour,
+            "lp_per_day": lp_per_hour * HOURS_PER_DAY,
+            "lp_per_month": lp_per_hour * HOURS_PER_MONTH,
+            "workers_by_city": by_city,
+            "average_secs_remaining": average_secs_remaining,
+            "soonest_expiry": soonest_expiry
+        }))
+    }
+    
+    pub(crate) fn verify_fleet_ssh(&self, endpoints: &serde_json::Map<String, Value>, concurrency: usize, timeout_ms: i64) -> ActionResult {
+        let workers = self.fetch_workers()?;
+        let timeout = std::time::Duration::from_millis(timeout_ms.max(1) as u64);
+        
+        let reports = run_bounded(&workers, concurrency, |worker| {
+            let Some((host, port)) = self.worker_ssh_endpoint(worker, endpoints) else {
+                return json!({
+                    "uuid": worker.uuid,
+                    "hostname": worker.hostname,
+                    "reachable": Value::Null,
+                    "error": "No SSH endpoint known for this worker"
+                });
+            };
+            
+            match tcp_probe(&host, port, timeout) {
+                Ok(latency_ms) => json!({
+                    "uuid": worker.uuid,
+                    "hostname": worker.hostname,
+                    "ssh_host": host,
+                    "ssh_port": port,
+                    "reachable": true,
+                    "latency_ms": latency_ms
+                }),
+                Err(e) => json!({
+                    "uuid": worker.uuid,
+                    "hostname": worker.hostname,
+                    "ssh_host": host,
+                    "ssh_port": port,
+                    "reachable": false,
+                    "error": e
+                }),
+            }
+        });
+        
+        let count = |reachable: Value| reports.iter().filter(|r| r["reachable"] == reachable).count();
+        let (reachable, unreachable, unknown) = (count(json!(true)), count(json!(false)), count(Value::Null));
+        
+        Ok(json!({
+            "success": true,
+            "all_reachable": unreachable == 0 && unknown == 0,
+            "total": reports.len(),
+            "reachable": reachable,
+            "unreachable": unreachable,
+            "unknown": unknown,
+            "workers": reports
+        }))
+    }
+    
+    pub(crate) fn fleet_utilization(&self, endpoints: &serde_json::Map<String, Value>, user: String, concurrency: usize) -> ActionResult {
+        let workers = self.fetch_workers()?;
+        
+        let reports = run_bounded(&workers, concurrency, |worker| {
+            let Some((host, port)) = self.worker_ssh_endpoint(worker, endpoints) else {
+                return json!({
+                    "uuid": worker.uuid,
+                    "hostname": worker.hostname,
+                    "reachable": Value::Null,
+                    "note": "Skipped: no SSH endpoint known for this worker"
+                });
+            };
+            let target = SshTarget {
+                host,
+                port: port as i64,
+                user: user.clone(),
+                jump_host: None,
+                connect_timeout_secs: self.setting_i64("ssh_connect_timeout_secs").filter(|&secs| secs > 0),
+                command_timeout_secs: self.setting_i64("ssh_command_timeout_secs").filter(|&secs| secs > 0),
+            };
+            
+            let mut metrics = match self.get_worker_metrics(&target) {
+                Ok(metrics) if metrics["reachable"] == json!(true) => metrics,
+                Ok(probe) => json!({
+                    "reachable": false,
+                    "note": format!("Skipped: unreachable over SSH: {}", probe["error"].as_str().unwrap_or_default())
+                }),
+                // The metrics command failing doesn't tell whether the worker answered
+                Err(e) => json!({
+                    "reachable": Value::Null,
+                    "error": e,
+                    "note": "Skipped: the metrics could not be read"
+                }),
+            };
+            if let Value::Object(map) = &mut metrics {
+                map.remove("success");
+            }
+            metrics["uuid"] = json!(worker.uuid);
+            metrics["hostname"] = json!(worker.hostname);
+            metrics
+        });
+        
+        let mut summary = summarize_utilization(&reports);
+        summary["success"] = json!(true);
+        summary["workers"] = json!(reports);
+        Ok(summary)
+    }
+    
+    pub(crate) fn list_expiring_soon(&self, within_hours: i64) -> ActionResult {
+        let threshold = within_hours * 3600;
+        let mut expiring = Vec::new();
+        let mut warnings = Vec::new();
+        
+        for worker in self.fetch_workers()? {
+            match parse_duration_secs(&worker.time_left) {
+                Some(seconds) if seconds < threshold => expiring.push((seconds.max(0), worker)),
+                Some(_) => {},
+                None => warnings.push(format!(
+                    "Excluded worker {} with unparseable time left '{}'",
+                    worker.uuid, worker.time_left
+                )),
+            }
+        }
+        
+        expiring.sort_by(|(a, wa), (b, wb)| a.cmp(b).then_with(|| wa.uuid.cmp(&wb.uuid)));
+        
+        let workers: Vec<Value> = expiring.iter()
+            .map(|(seconds, worker)| json!({
+                "uuid": worker.uuid,
+                "hostname": worker.hostname,
+                "time_left": worker.time_left,
+                "seconds_remaining": seconds,
+                "lp_per_hour": worker.lp_per_hour
+            }))
+            .collect();
+        
+        Ok(json!({
+            "success": true,
+            "within_hours": within_hours,
+            "count": workers.len(),
+            "workers": workers,
+            "warnings": warnings
+        }))
+    }
+    
+    pub(crate) fn export_csv(&self) -> ActionResult {
+        let workers = self.fetch_workers()?;
+        
+        let mut csv = String::from("city,uuid,hostname,cores,memory_mb,disk_gb,lp_per_hour,time_left\r\n");
+        for worker in &workers {
+            let fields = [
+                worker.city.clone(),
+                worker.uuid.clone(),
+                worker.hostname.clone(),
+                worker.cores.to_string(),
+                worker.memory_mb.to_string(),
+                worker.disk_gb.to_string(),
+                worker.lp_per_hour.to_string(),
+                worker.time_left.clone(),
+            ];
+            csv.push_str(&fields.iter().map(|field| csv_field(field)).collect::<Vec<_>>().join(","));
+            csv.push_str("\r\n");
+        }
+        
+        Ok(json!({
+            "success": true,
+            "rows": workers.len(),
+            "csv": csv
+        }))
+    }
+    
+    // Worker specs of the "workers" array parameter, each resolved through its preset
+    pub(crate) fn extract_worker_specs(&self, params: &HashMap<String, Value>) -> Result<Vec<WorkerSpec>, String> {
+        let workers = validation::extract_json(params, "workers")?;
+        workers.as_array()
+            .ok_or_else(|| "Parameter 'workers' must be an array".to_string())?
+            .iter()
+            .map(|w| {
+                let spec_params: HashMap<String, Value> = serde_json::from_value(w.clone())
+                    .map_err(|_| "Each entry in 'workers' must be an object".to_string())?;
+                WorkerSpec::from_params(&self.apply_preset(&spec_params)?)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_fields_are_quoted_only_when_needed() {
+        assert_eq!(csv_field("brave-fox"), "brave-fox");
+        assert_eq!(csv_field("San Jose, CA"), "\"San Jose, CA\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_field(""), "");
+    }
+
+    #[test]
+    fn fleet_diff_reports_added_removed_resized_and_renewed_workers() {
+        let before = json!({ "workers": [
+            { "uuid": "a", "hostname": "kept", "cores": 2, "memory_mb": 2048, "disk_gb": 20, "lp_per_hour": 1.0, "time_left": "2h" },
+            { "uuid": "b", "hostname": "gone", "cores": 1, "memory_mb": 1024, "disk_gb": 10, "lp_per_hour": 0.5, "time_left": "5h" }
+        ]});
+        let after = json!([
+            { "uuid": "a", "hostname": "kept", "cores": 4, "memory_mb": 2048, "disk_gb": 20, "lp_per_hour": 2.0, "time_left": "1d" },
+            { "uuid": "c", "hostname": "new", "cores": 1, "memory_mb": 1024, "disk_gb": 10, "lp_per_hour": 0.25, "time_left": "3h" }
+        ]);
+        
+        let diff = fleet_diff(&before, &after).unwrap();
+        assert_eq!(diff["changed"], true);
+        assert_eq!(diff["added"][0]["uuid"], "c");
+        assert_eq!(diff["removed"][0]["uuid"], "b");
+        assert_eq!(diff["resized"][0]["changes"], json!({ "cores": { "before": 2, "after": 4 } }));
+        assert_eq!(diff["renewed"][0]["time_left_after"], "1d");
+        assert_eq!(diff["cost_impact"]["added_lp_per_hour"], 1.25);
+        assert_eq!(diff["cost_impact"]["removed_lp_per_hour"], 0.5);
+        assert_eq!(diff["cost_impact"]["net_lp_per_hour"], 0.75);
+        
+        assert_eq!(fleet_diff(&before, &before).unwrap()["changed"], false);
+        assert!(fleet_diff(&json!({}), &after).is_err());
+        assert!(fleet_diff(&json!([{ "hostname": "x" }]), &after).is_err());
+    }
+
+    #[test]
+    fn expiry_timeline_follows_the_installed_clock() {
+        let extension = DeeTeeExtension::new();
+        extension.default_settings.write().unwrap().insert("execution_mode".to_string(), json!("native"));
+        extension.set_clock(Arc::new(MockClock::new(1_000_000)));
+        let runner = Arc::new(MockRunner::new());
+        runner.respond("vm list", 0, concat!(
+            "| City | UUID | Hostname | Cores | Mem (MB) | Disk (GB) | LP/h | Time left |\n",
+            "|------|------|----------|-------|----------|-----------|------|-----------|\n",
+            "| Oslo | 9c8b7d6e-5f4a-4b2c-8e5a-0b7e2a4c1f3d | calm-owl | 4 | 4096 | 40 | 2 | 1d |\n",
+            "| Berlin | 0b7e2a4c-1f3d-4e5a-9c8b-7d6e5f4a3b2c | brave-fox | 2 | 2048 | 20 | 1 | 3h 20m |\n",
+            "| Rome | 5f4a3b2c-0b7e-4a4c-9f3d-7d6e1f3d9c8b | lost-elk | 1 | 1024 | 10 | 1 | soon |\n",
+        ), "");
+        extension.set_command_runner(Some(runner));
+        
+        let result = extension.execute_action("expiry_timeline", &HashMap::new()).unwrap();
+        assert_eq!(result["now"], 1_000_000);
+        assert_eq!(result["nearest_expiry"]["hostname"], "brave-fox");
+        assert_eq!(result["nearest_expiry"]["expires_at"], 1_000_000 + 12_000);
+        assert_eq!(result["timeline"][1]["expires_at"], 1_000_000 + 86_400);
+        assert_eq!(result["warnings"].as_array().unwrap().len(), 1);
+    }
+
+    // `vm list` output with one worker about to expire and one with a day left
+    const EXPIRING_WORKER_LIST: &str = concat!(
+        "| City | UUID | Hostname | Cores | Mem (MB) | Disk (GB) | LP/h | Time left |\n",
+        "|------|------|----------|-------|----------|-----------|------|-----------|\n",
+        "| Oslo | 9c8b7d6e-5f4a-4b2c-8e5a-0b7e2a4c1f3d | calm-owl | 4 | 4096 | 40 | 2 | 1d |\n",
+        "| Berlin | 0b7e2a4c-1f3d-4e5a-9c8b-7d6e5f4a3b2c | brave-fox | 2 | 2048 | 20 | 1 | 3h 20m |\n",
+    );
+
+    #[test]
+    fn auto_renew_extends_only_workers_below_the_threshold() {
+        let (extension, runner, _dir) = crate::tests::mocked_extension();
+        runner.respond("vm list", 0, EXPIRING_WORKER_LIST, "");
+        runner.respond("vm update", 0, "VM updated\n", "");
+        
+        let params = HashMap::from([("threshold_hours".to_string(), json!(12)), ("extend_hours".to_string(), json!(8))]);
+        let result = extension.execute_action("auto_renew", &params).unwrap();
+        assert_eq!(result["renewed"].as_array().unwrap().len(), 1);
+        assert_eq!(result["renewed"][0]["hostname"], "brave-fox");
+        assert_eq!(result["skipped"][0]["uuid"], "9c8b7d6e-5f4a-4b2c-8e5a-0b7e2a4c1f3d");
+        let updates: Vec<String> = runner.calls().into_iter().filter(|call| call.contains("vm update")).collect();
+        assert_eq!(updates, ["detee-cli vm update --hours 8 0b7e2a4c-1f3d-4e5a-9c8b-7d6e5f4a3b2c"]);
+        
+        for threshold in [0, -5, i64::MAX] {
+            let params = HashMap::from([("threshold_hours".to_string(), json!(threshold)), ("extend_hours".to_string(), json!(8))]);
+            assert!(extension.execute_action("auto_renew", &params).is_err(), "{}", threshold);
+        }
+    }
+}
@@ -0,0 +1,999 @@
+// File: cpi_detee/src/parsing.rs
+//! Parsing of DeeTEE CLI output: tables, amounts, durations and the free-form messages the CLI prints
+
+use super::*;
+
+// Remove ANSI escape sequences (colors, cursor movement, terminal titles) from command output
+pub(crate) fn strip_ansi(text: &str) -> String {
+    if !text.contains('\x1b') {
+        return text.to_string();
+    }
+    STRIP_ANSI_RE.replace_all(text, "").to_string()
+}
+
+// Keep only the final state of lines redrawn with carriage returns (spinners, progress bars).
+// CRLF line endings are kept as plain line breaks.
+pub(crate) fn collapse_carriage_returns(text: &str) -> String {
+    if !text.contains('\r') {
+        return text.to_string();
+    }
+    text.split('\n')
+        .map(|line| {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            // A redraw that ended with '\r' still leaves its last non-empty state on screen
+            line.rsplit('\r').find(|state| !state.trim().is_empty()).unwrap_or("")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Whether the "output_format" parameter asks for flattened output; "nested" is the default
+pub(crate) fn extract_flat_output(params: &HashMap<String, Value>) -> Result<bool, String> {
+    match validation::extract_string_opt(params, "output_format")?.as_deref().map(str::trim) {
+        None | Some("") | Some("nested") => Ok(false),
+        Some("flat") => Ok(true),
+        Some(other) => Err(format!("Parameter 'output_format' must be 'nested' or 'flat', got '{}'", other)),
+    }
+}
+
+// Flatten nested objects and arrays into one object with dotted keys such as "vm.cores" or
+// "workers.0.uuid". Empty objects and arrays are kept as values so they don't disappear.
+pub(crate) fn flatten_json(value: Value) -> Value {
+    fn flatten_into(prefix: String, value: Value, out: &mut serde_json::Map<String, Value>) {
+        let join = |key: &str| if prefix.is_empty() { key.to_string() } else { format!("{}.{}", prefix, key) };
+        match value {
+            Value::Object(map) if !map.is_empty() => {
+                for (key, value) in map {
+                    flatten_into(join(&key), value, out);
+                }
+            },
+            Value::Array(list) if !list.is_empty() => {
+                for (index, value) in list.into_iter().enumerate() {
+                    flatten_into(join(&index.to_string()), value, out);
+                }
+            },
+            other => {
+                out.insert(prefix, other);
+            },
+        }
+    }
+    
+    let mut out = serde_json::Map::new();
+    flatten_into(String::new(), value, &mut out);
+    Value::Object(out)
+}
+
+static TRANSACTION_RE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"(?i)\b(?:transaction|tx)(?:\s*(?:id|hash|signature))?\s*[:=]\s*([A-Za-z0-9]{16,128})\b").unwrap());
+
+static TRANSACTION_HASH_RE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"\b0x[0-9a-fA-F]{64}\b").unwrap());
+
+static RECEIPT_RE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"(?i)\breceipt(?:\s*id)?\s*[:=]\s*([A-Za-z0-9_-]{4,128})").unwrap());
+
+// Find the transaction ID and receipt a deploy printed, as in "Transaction: 5Kd3...", "tx hash =
+// 0xab12..." or "Receipt ID: r-1234"; a bare 0x-prefixed 32-byte hash also counts as a transaction
+fn parse_transaction_ids(output: &str) -> (Option<String>, Option<String>) {
+    
+    let transaction = TRANSACTION_RE.captures(output)
+        .map(|caps| caps[1].to_string())
+        .or_else(|| TRANSACTION_HASH_RE.find(output).map(|m| m.as_str().to_string()));
+    let receipt = RECEIPT_RE.captures(output).map(|caps| caps[1].to_string());
+    (transaction, receipt)
+}
+
+// Case-insensitive regex for a marker phrase that tolerates any spacing between its words and
+// only matches whole words, so "VM CREATED" also finds "VM Created!" or "vm created successfully"
+fn marker_regex(marker: &str) -> Option<regex::Regex> {
+    let words: Vec<String> = marker.split_whitespace().map(regex::escape).collect();
+    if words.is_empty() {
+        return None;
+    }
+    let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+    let start = if is_word(marker.trim().chars().next()) { r"\b" } else { "" };
+    let end = if is_word(marker.trim().chars().last()) { r"\b" } else { "" };
+    regex::Regex::new(&format!(r"(?i){}{}{}", start, words.join(r"\s+"), end)).ok()
+}
+
+// Whether output holds the marker as whole words. An empty marker, e.g. an override meant to
+// disable it, matches nothing.
+pub(crate) fn contains_marker(output: &str, marker: &str) -> bool {
+    marker_regex(marker).is_some_and(|re| re.is_match(output))
+}
+
+// First trimmed line of output holding one of the markers as whole words, so "error" finds
+// "Error: no funds" but not "0 errors" or "errorless"
+pub(crate) fn marker_line<'a>(output: &'a str, markers: &[String]) -> Option<&'a str> {
+    let patterns: Vec<regex::Regex> = markers.iter().filter_map(|marker| marker_regex(marker)).collect();
+    output.lines()
+        .map(|line| line.trim())
+        .find(|line| patterns.iter().any(|re| re.is_match(line)))
+}
+
+// UUID pattern shared by the hostname patterns below
+const UUID_PATTERN: &str = r"[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}";
+
+pub(crate) static UUID_RE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(&format!("^{}$", UUID_PATTERN)).unwrap());
+
+// Hostname patterns tried in order; the last one, a bare name, only when `bare` names are accepted
+static ECHOED_HOSTNAME_RES: LazyLock<[regex::Regex; 3]> = LazyLock::new(|| [
+    regex::Regex::new(r#"(?i)\b(?:host)?name\s*[:=]?\s*["'`]?([a-zA-Z0-9][a-zA-Z0-9_.-]*)"#).unwrap(),
+    regex::Regex::new(&format!(r#"^[\s:]*["'`]?([a-zA-Z0-9][a-zA-Z0-9_.-]*)["'`]?\s*\(\s*{}\s*\)"#, UUID_PATTERN)).unwrap(),
+    regex::Regex::new(r#"^[\s:]*["'`]?([a-zA-Z0-9][a-zA-Z0-9_.-]*)["'`]?\s*$"#).unwrap(),
+]);
+
+// Find a hostname echoed after a marker, as in "VM CREATED with name web-1",
+// "VM CREATED: web-1 (<uuid>)" or, when `bare` names are accepted, "Deployed VM 'web-1'"
+fn echoed_hostname(rest: &str, bare: bool) -> Option<String> {
+    ECHOED_HOSTNAME_RES.iter()
+        .take(if bare { 3 } else { 2 })
+        .filter_map(|re| re.captures(rest))
+        .map(|caps| caps[1].to_string())
+        .find(|name| !UUID_RE.is_match(name))
+}
+
+// Status fragments that mark a VM as broken (crashed, error, deploy-failed, ...)
+const FAILED_STATUS_MARKERS: &[&str] = &["crash", "error", "fail"];
+
+// The failure reason for a VM status that indicates a broken VM, e.g. "error: out of memory"
+pub(crate) fn failure_reason(status: &str) -> Option<String> {
+    let lower = status.to_lowercase();
+    if !FAILED_STATUS_MARKERS.iter().any(|marker| lower.contains(marker)) {
+        return None;
+    }
+    let reason = status.split_once(':').map(|(_, reason)| reason.trim()).unwrap_or(status.trim());
+    Some(if reason.is_empty() { status.trim() } else { reason }.to_string())
+}
+
+static DIGEST_RE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"sha256:[0-9a-f]{64}").unwrap());
+
+// Pull the "sha256:..." digest out of docker output such as "detee/detee-cli@sha256:..."
+pub(crate) fn extract_digest(text: &str) -> Option<String> {
+    DIGEST_RE.find(text).map(|m| m.as_str().to_string())
+}
+
+static LP_AMOUNT_RE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"(\(\s*)?(-\s*)?(\d+(?:\.\d+)?)").unwrap());
+
+// Extract the numeric amount from an LP value such as "1,234.5 LP". Debts may be written
+// "-12 LP", "- 12 LP", with a unicode minus sign or in accounting style as "(12 LP)".
+pub(crate) fn parse_lp_amount(text: &str) -> Option<f64> {
+    let cleaned = text.replace(',', "").replace('\u{2212}', "-");
+    let caps = LP_AMOUNT_RE.captures(&cleaned)?;
+    let amount: f64 = caps[3].parse().ok()?;
+    let negative = caps.get(1).is_some() || caps.get(2).is_some();
+    // Avoid reporting "-0 LP" as a negative zero
+    Some(if negative && amount != 0.0 { -amount } else { amount })
+}
+
+static LOCKED_AMOUNT_RE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"\d[\d,]*(?:\.\d+)?").unwrap());
+
+// Find the amount of LP locked by a deploy on the line holding the "locking" marker, wherever
+// the number sits, as in "Locking 42.5 LP", "Now locking 1,200 LP" or "Locking funds: 42.5 LP"
+fn parse_locked_lp(output: &str, marker: &str) -> Option<f64> {
+    let marker_re = marker_regex(marker)?;
+    output.lines().find_map(|line| {
+        let rest = &line[marker_re.find(line)?.end()..];
+        LOCKED_AMOUNT_RE.find(rest)?.as_str().replace(',', "").parse().ok()
+    })
+}
+
+static NEGATION_RE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"(?i)^(?:not|never|cannot|can't|won't|wasn't|isn't|will|to|pending|scheduled)$").unwrap());
+
+// Whether the CLI reported a deletion: a line holding the "vm_deleted" marker as a whole word
+// whose two preceding words don't negate or defer it, so "not deleted" and "will be deleted"
+// don't count
+pub(crate) fn deletion_confirmed(output: &str, marker: &str) -> bool {
+    let Some(marker_re) = marker_regex(marker) else {
+        return false;
+    };
+    output.lines().any(|line| {
+        marker_re.find(line).is_some_and(|m| {
+            !line[..m.start()].split_whitespace().rev().take(2)
+                .any(|word| NEGATION_RE.is_match(word.trim_matches(|c: char| !c.is_alphanumeric() && c != '\'')))
+        })
+    })
+}
+
+// Split a table row into its trimmed cells, dropping only the outer borders so
+// empty cells keep their position
+fn split_table_row(line: &str) -> Vec<String> {
+    let trimmed = line.trim();
+    let inner = trimmed.strip_prefix('|').unwrap_or(trimmed);
+    let inner = inner.strip_suffix('|').unwrap_or(inner);
+    inner.split('|').map(|cell| cell.trim().to_string()).collect()
+}
+
+static TABLE_NUMBER_RE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"^-?\d+(?:\.\d+)?").unwrap());
+
+// Parse a table cell such as "2,048" or "20 GB" as a number, reporting whether
+// separators or a unit suffix had to be stripped to do so
+fn parse_table_number(text: &str) -> Option<(f64, bool)> {
+    let text = text.trim();
+    let cleaned = text.replace([',', '_'], "");
+    let number = TABLE_NUMBER_RE.find(&cleaned)?.as_str();
+    Some((number.parse().ok()?, number != text))
+}
+
+static NUMERIC_CELL_RE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"^-?[\d,_]+(?:\.\d+)?\s*[A-Za-z/]*$").unwrap());
+
+// Parse a pipe table such as the CLI's node listing into one object per row, keyed by the
+// snake_cased header. Cells holding a plain number, optionally with a unit ("8 GB"), become numbers.
+pub(crate) fn parse_generic_table(output: &str) -> Vec<Value> {
+    let mut rows = output.lines()
+        .filter(|line| line.contains('|'))
+        .map(split_table_row)
+        .filter(|cells| cells.iter().any(|cell| !cell.is_empty() && !cell.chars().all(|c| "-=+:".contains(c))));
+    
+    let Some(header) = rows.next() else {
+        return Vec::new();
+    };
+    let keys: Vec<String> = header.iter()
+        .map(|name| name.to_lowercase().split_whitespace().collect::<Vec<_>>().join("_"))
+        .collect();
+    
+    rows.filter(|cells| cells.len() == keys.len())
+        .map(|cells| {
+            let record: serde_json::Map<String, Value> = keys.iter()
+                .zip(cells)
+                .map(|(key, cell)| {
+                    let value = match parse_table_number(&cell) {
+                        Some((number, _)) if NUMERIC_CELL_RE.is_match(&cell) => json!(number),
+                        _ => json!(cell),
+                    };
+                    (key.clone(), value)
+                })
+                .collect();
+            Value::Object(record)
+        })
+        .collect()
+}
+
+static WORKER_EVENT_RE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"^\s*\[?(\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}(?::\d{2}(?:\.\d+)?)?(?:Z|[+-]\d{2}:?\d{2})?)\]?\s*[-:|]?\s*(.+?)\s*$").unwrap());
+
+// Parse worker event output, either a table or one "<timestamp> <message>" line per event,
+// into records with "timestamp" and "message" fields
+pub(crate) fn parse_worker_events(output: &str) -> Vec<Value> {
+    
+    // Event lines may use pipes too, so only read a table when it starts with a header row
+    let starts_with_event = output.lines().find(|line| !line.trim().is_empty()).is_some_and(|line| WORKER_EVENT_RE.is_match(line));
+    let table = if starts_with_event { Vec::new() } else { parse_generic_table(output) };
+    if !table.is_empty() {
+        return table.into_iter()
+            .map(|mut record| {
+                let timestamp = node_field(&record, &["timestamp", "time", "date"]).cloned().unwrap_or(Value::Null);
+                let message = node_field(&record, &["message", "event", "type", "description"]).cloned().unwrap_or(Value::Null);
+                record["timestamp"] = timestamp;
+                record["message"] = message;
+                record
+            })
+            .collect();
+    }
+    
+    output.lines()
+        .filter_map(|line| WORKER_EVENT_RE.captures(line))
+        .map(|caps| json!({ "timestamp": &caps[1], "message": &caps[2] }))
+        .collect()
+}
+
+// Every line a command printed, stdout first, with sensitive values redacted
+pub(crate) fn transcript_lines(output: &CommandOutput) -> Vec<String> {
+    redact_sensitive(&output.stdout)
+        .lines()
+        .chain(redact_sensitive(&output.stderr).lines())
+        .map(|line| line.to_string())
+        .collect()
+}
+
+// Redacted, single-line excerpt of command output for use in error messages
+pub(crate) fn output_snippet(output: &str, max_chars: usize) -> String {
+    let redacted = redact_sensitive(output.trim()).replace('\n', " | ");
+    if redacted.chars().count() <= max_chars {
+        return redacted;
+    }
+    let truncated: String = redacted.chars().take(max_chars).collect();
+    format!("{}...", truncated)
+}
+
+static RFC3339_RE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"^(\d{4})-(\d{2})-(\d{2})[Tt ](\d{2}):(\d{2}):(\d{2})(?:\.\d+)?(?:([Zz])|([+-])(\d{2}):(\d{2}))$").unwrap());
+
+// Parse an RFC3339 timestamp such as "2024-05-01T12:34:56.123Z" into seconds since the Unix epoch
+pub(crate) fn parse_rfc3339(text: &str) -> Option<i64> {
+    let caps = RFC3339_RE.captures(text.trim())?;
+    let num = |i: usize| caps[i].parse::<i64>().ok();
+    
+    let (year, month, day) = (num(1)?, num(2)?, num(3)?);
+    let (hour, minute, second) = (num(4)?, num(5)?, num(6)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    
+    // Days since the epoch using the proleptic Gregorian calendar
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+    
+    let offset = match caps.get(8) {
+        Some(sign) => {
+            let offset = num(9)? * 3600 + num(10)? * 60;
+            if sign.as_str() == "-" { -offset } else { offset }
+        },
+        None => 0,
+    };
+    
+    Some(days * 86400 + hour * 3600 + minute * 60 + second - offset)
+}
+
+static DURATION_PART_RE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"(\d+(?:\.\d+)?)\s*([a-z]*)").unwrap());
+
+// Convert a duration such as "3h 20m", "2 days 4 hours" or "03:20:00" into seconds.
+// A bare number is interpreted as hours, matching the CLI's `--hours` flag.
+pub(crate) fn parse_duration_secs(text: &str) -> Option<i64> {
+    let text = text.trim().to_lowercase();
+    if text.is_empty() {
+        return None;
+    }
+    if text.contains("expired") {
+        return Some(0);
+    }
+    
+    let (sign, text) = match text.strip_prefix('-') {
+        Some(rest) => (-1, rest.trim()),
+        None => (1, text.as_str()),
+    };
+    
+    // Clock format: HH:MM or HH:MM:SS
+    if text.contains(':') {
+        let parts: Vec<i64> = text.split(':')
+            .map(|p| p.trim().parse::<i64>())
+            .collect::<Result<_, _>>()
+            .ok()?;
+        let secs = match parts.as_slice() {
+            [h, m] => h * 3600 + m * 60,
+            [h, m, s] => h * 3600 + m * 60 + s,
+            _ => return None,
+        };
+        return Some(sign * secs);
+    }
+    
+    let mut total = 0.0;
+    let mut matched = false;
+    for caps in DURATION_PART_RE.captures_iter(text) {
+        let amount: f64 = caps[1].parse().ok()?;
+        let unit_secs = match &caps[2] {
+            "d" | "day" | "days" => 86400.0,
+            "" | "h" | "hr" | "hrs" | "hour" | "hours" => 3600.0,
+            "m" | "min" | "mins" | "minute" | "minutes" => 60.0,
+            "s" | "sec" | "secs" | "second" | "seconds" => 1.0,
+            _ => return None,
+        };
+        total += amount * unit_secs;
+        matched = true;
+    }
+    
+    if matched {
+        Some(sign * total.round() as i64)
+    } else {
+        None
+    }
+}
+
+impl DeeTeeExtension {
+    // Parse table output from DeeTEE CLI into a vector of WorkerInfo
+    pub(crate) fn parse_workers_table(&self, output: &str) -> Vec<WorkerInfo> {
+        let mut workers = Vec::new();
+        
+        // Split the output by lines
+        let lines: Vec<&str> = output.lines()
+            .filter(|line| line.contains("|"))  // Only consider lines with pipe characters
+            .collect();
+        
+        // Newer CLI versions may add GPU and status columns, so locate them by their headers
+        let header: Vec<String> = lines.first().map(|header| split_table_row(header)).unwrap_or_default();
+        let header_column = |matches: &dyn Fn(&str) -> bool| {
+            header.iter().position(|name| matches(&name.to_lowercase()))
+        };
+        let gpu_column = header_column(&|name| name.contains("gpu"));
+        let status_column = header_column(&|name| name == "status" || name == "state");
+        
+        // Bound the work a pathological or hostile listing can cause
+        let max_rows = self.setting_i64("max_table_rows")
+            .map(|rows| rows.max(0) as usize)
+            .unwrap_or(DEFAULT_MAX_TABLE_ROWS);
+        
+        // Skip the header lines (first 2 lines) and separator line
+        for line in lines.iter().skip(2) {
+            // Skip separator lines
+            if line.contains("----") {
+                continue;
+            }
+            
+            if workers.len() >= max_rows {
+                self.log(&format!("Warning: worker table has more than {} rows; ignoring the rest", max_rows));
+                mark_output_truncated();
+                break;
+            }
+            
+            // Split the line by the pipe character and trim whitespace
+            let mut cells = split_table_row(line);
+            
+            // A city such as "San Jose | CA" spills into extra cells, so fold them back into the first column
+            if cells.len() > header.len() && !header.is_empty() {
+                let extra = cells.len() - header.len();
+                let city = cells[..=extra].join(" | ");
+                cells.splice(..=extra, [city]);
+            }
+            let columns: Vec<&str> = cells.iter().map(|cell| cell.as_str()).collect();
+            
+            // Skip if we don't have enough columns (city through time left)
+            if columns.len() < 8 {
+                continue;
+            }
+            
+            // Parse the worker information from columns, tolerating separators and units
+            let mut warnings = Vec::new();
+            let mut number = |field: &str, text: &str| match parse_table_number(text) {
+                Some((value, stripped)) => {
+                    if stripped {
+                        warnings.push(format!("{} '{}' was read as {}", field, text, value));
+                    }
+                    value
+                }
+                None => {
+                    warnings.push(format!("{} '{}' is not a number", field, text));
+                    0.0
+                }
+            };
+            let cores = number("cores", columns[3]) as i64;
+            let memory_mb = number("memory_mb", columns[4]) as i64;
+            let disk_gb = number("disk_gb", columns[5]) as i64;
+            let lp_per_hour = number("lp_per_hour", columns[6]);
+            let status = status_column
+                .and_then(|i| columns.get(i))
+                .filter(|status| !status.is_empty() && **status != "-")
+                .map(|status| status.to_string());
+            let reason = status.as_deref().and_then(failure_reason);
+            let worker = WorkerInfo {
+                city: columns[0].to_string(),
+                uuid: normalize_worker_id(columns[1]),
+                hostname: columns[2].to_string(),
+                cores,
+                memory_mb,
+                disk_gb,
+                lp_per_hour,
+                lp_per_day: lp_per_hour * HOURS_PER_DAY,
+                lp_per_month: lp_per_hour * HOURS_PER_MONTH,
+                time_left: columns[7].to_string(),
+                gpu: gpu_column
+                    .and_then(|i| columns.get(i))
+                    .filter(|gpu| !gpu.is_empty() && **gpu != "-")
+                    .map(|gpu| gpu.to_string()),
+                warnings,
+                failed: reason.is_some(),
+                failure_reason: reason,
+                status,
+            };
+            
+            workers.push(worker);
+        }
+        
+        workers
+    }
+    
+    // Parse the command names listed in the "Commands:" section of CLI help output
+    pub(crate) fn parse_help_commands(&self, output: &str) -> Vec<String> {
+        let mut commands = Vec::new();
+        let mut in_commands = false;
+        
+        for line in output.lines() {
+            let trimmed = line.trim();
+            
+            if !in_commands {
+                // clap 3+ uses "Commands:", older versions use "SUBCOMMANDS:"
+                if trimmed.eq_ignore_ascii_case("commands:") || trimmed.eq_ignore_ascii_case("subcommands:") {
+                    in_commands = true;
+                }
+                continue;
+            }
+            
+            // The section ends at the first blank or non-indented line
+            if trimmed.is_empty() || !line.starts_with(char::is_whitespace) {
+                break;
+            }
+            
+            if let Some(name) = trimmed.split_whitespace().next() {
+                if name != "help" {
+                    commands.push(name.to_string());
+                }
+            }
+        }
+        
+        commands
+    }
+    
+    // Parse the "Key: value" lines printed by `detee-cli vm info` into worker details
+    pub(crate) fn parse_vm_info(&self, output: &str) -> Option<Value> {
+        let mut vm_info = json!({});
+        let mut found = 0;
+        
+        for line in output.lines() {
+            let Some((key, value)) = line.split_once(':') else { continue };
+            let key = key.trim().to_lowercase().replace([' ', '-'], "_");
+            let value = value.trim();
+            if key.is_empty() || value.is_empty() {
+                continue;
+            }
+            
+            let field = VM_INFO_FIELDS.iter()
+                .find(|(_, keys)| keys.contains(&key.as_str()))
+                .map(|(field, _)| field.to_string())
+                .unwrap_or(key);
+            if vm_info.get(&field).is_some() {
+                continue;
+            }
+            
+            vm_info[&field] = if VM_INFO_NUMERIC.contains(&field.as_str()) {
+                match parse_table_number(value) {
+                    Some((number, _)) if field == "lp_per_hour" => json!(number),
+                    Some((number, _)) => json!(number as i64),
+                    None => json!(value),
+                }
+            } else if field == "uuid" {
+                json!(normalize_worker_id(value))
+            } else {
+                json!(value)
+            };
+            found += 1;
+        }
+        
+        let reason = vm_info.get("status").and_then(|s| s.as_str()).and_then(failure_reason);
+        vm_info["failed"] = json!(reason.is_some());
+        if let Some(reason) = reason {
+            vm_info["failure_reason"] = json!(reason);
+        }
+        
+        if let Some(lp_per_hour) = vm_info["lp_per_hour"].as_f64() {
+            vm_info["lp_per_day"] = json!(lp_per_hour * HOURS_PER_DAY);
+            vm_info["lp_per_month"] = json!(lp_per_hour * HOURS_PER_MONTH);
+        }
+        
+        // Stray log lines can contain colons too, so require a couple of fields
+        if found >= 2 {
+            Some(vm_info)
+        } else {
+            None
+        }
+    }
+    
+    // Parse account information by matching each field against its known label aliases
+    fn parse_account_info(&self, output: &str) -> Option<Value> {
+        let mut account_info = json!({});
+        let mut found = 0;
+        
+        for (field, _) in ACCOUNT_LABELS {
+            let labels = self.account_labels(field);
+            
+            'lines: for line in output.lines() {
+                let lower = line.to_lowercase();
+                for label in &labels {
+                    let label = label.to_lowercase();
+                    if let Some(rest) = lower.find(&label).and_then(|idx| line.get(idx + label.len()..)) {
+                        account_info[*field] = json!(rest.trim_start_matches(':').trim());
+                        found += 1;
+                        break 'lines;
+                    }
+                }
+            }
+        }
+        
+        // A single matching label could be a coincidence, so require at least two fields
+        if found >= 2 {
+            Some(account_info)
+        } else {
+            None
+        }
+    }
+    
+    // Collect the lines of CLI output that contain one of the known warning markers
+    pub(crate) fn extract_warnings(&self, output: &str) -> Vec<String> {
+        let markers: Vec<String> = match self.setting("warning_markers") {
+            Some(Value::Array(markers)) => markers.iter()
+                .filter_map(|m| m.as_str())
+                .map(|m| m.to_lowercase())
+                .collect(),
+            _ => DEFAULT_WARNING_MARKERS.iter().map(|m| m.to_string()).collect(),
+        };
+        
+        output.lines()
+            .map(|line| line.trim())
+            .filter(|line| {
+                let lower = line.to_lowercase();
+                markers.iter().any(|m| lower.contains(m.as_str()))
+            })
+            .map(|line| line.to_string())
+            .collect()
+    }
+    
+    // Check whether the CLI reported that the account has no VMs at all
+    pub(crate) fn is_no_vms_output(&self, output: &str) -> bool {
+        let marker = self.marker("no_vms").to_lowercase();
+        !marker.is_empty() && output.to_lowercase().contains(&marker)
+    }
+    
+    // Convert CLI text output to a JSON structure based on patterns
+    pub(crate) fn cli_output_to_json(&self, output: &str, file_path: &Path) -> Result<Value, String> {
+        // For any other output, just return a success flag
+        match self.match_cli_output(output, file_path)? {
+            Some((_, value)) => Ok(value),
+            None => self.generic_output_result(output),
+        }
+    }
+    
+    // Parse captured command output, using stderr to explain output no pattern recognised
+    pub(crate) fn parse_command_output(&self, output: &CommandOutput) -> Result<Value, String> {
+        match self.match_cli_output(&output.stdout, &PathBuf::new())? {
+            Some((branch, value)) => {
+                self.emit(ProgressEvent::Parsed { branch: branch.to_string() });
+                Ok(value)
+            },
+            None if !output.stderr.trim().is_empty() => Err(format!(
+                "Unrecognized DeeTEE CLI output: {}", output.stderr.trim()
+            )),
+            None => self.generic_output_result(&output.stdout),
+        }
+    }
+    
+    // Result for output no pattern recognised: a plain success flag, unless strict parsing
+    // is enabled, in which case unrecognised output is treated as parser drift
+    fn generic_output_result(&self, output: &str) -> Result<Value, String> {
+        if self.setting_bool("strict_parsing") {
+            return Err(format!(
+                "Unrecognized DeeTEE CLI output (strict parsing): \"{}\"",
+                output_snippet(output, 200)
+            ));
+        }
+        
+        // Unrecognized output that reads like a failure must not pass as a success
+        if let Some(line) = self.failure_line(output) {
+            return Err(format!("DeeTEE CLI reported a failure: {}", output_snippet(line, 200)));
+        }
+        
+        Ok(json!({
+            "success": true
+        }))
+    }
+    
+    // First line of CLI output containing one of the "failure_markers"
+    pub(crate) fn failure_line<'a>(&self, output: &'a str) -> Option<&'a str> {
+        let markers: Vec<String> = match self.setting("failure_markers") {
+            Some(Value::Array(markers)) => markers.iter()
+                .filter_map(|m| m.as_str())
+                .map(|m| m.to_string())
+                .collect(),
+            _ => DEFAULT_FAILURE_MARKERS.iter().map(|m| m.to_string()).collect(),
+        };
+        marker_line(output, &markers)
+    }
+    
+    // Match CLI text output against the known patterns, returning the name of the matching
+    // parse branch with the extracted data, or None when nothing matched
+    fn match_cli_output(&self, output: &str, _file_path: &Path) -> Result<Option<(&'static str, Value)>, String> {
+        // This method would need to be customized for each command output format
+        // The implementation below is a simplified example
+
+        // Newer CLI versions may already emit JSON, which needs no heuristics
+        let trimmed = output.trim();
+        if trimmed.starts_with('{') || trimmed.starts_with('[') {
+            if let Ok(value) = serde_json::from_str::<Value>(trimmed) {
+                return Ok(Some(("json", value)));
+            }
+        }
+
+        // Check for version information
+        let version_marker = self.marker("version");
+        if output.contains(&version_marker) {
+            let version = output.trim()
+                .replace(&format!("{} ", version_marker), "")
+                .trim()
+                .to_string();
+                
+            return Ok(Some(("version", json!({
+                "version": version,
+                "success": true
+            }))));
+        }
+        
+        // Check for container ID
+        if trimmed.len() == 64 || trimmed.len() == 12 {
+            // Likely a container ID (either full or short format)
+            return Ok(Some(("container_id", json!({
+                "container_id": output.trim()
+            }))));
+        }
+        
+        // Check for account information
+        if let Some(account_info) = self.parse_account_info(output) {
+            return Ok(Some(("account_info", account_info)));
+        }
+        
+        // Check for VM creation output
+        let created_marker = self.marker("vm_created");
+        let created_re = marker_regex(&created_marker);
+        if created_re.as_ref().is_some_and(|re| re.is_match(output)) {
+            let mut vm_info = json!({});
+            
+            // Extract hostname
+            if let Some(line) = output.lines().find(|l| l.contains(&self.marker("random_vm_name"))) {
+                let parts: Vec<&str> = line.splitn(2, ':').collect();
+                if parts.len() >= 2 {
+                    vm_info["hostname"] = json!(parts[1].trim());
+                }
+            }
+            
+            // An explicitly chosen name is echoed on the deploy or VM CREATED line instead
+            if vm_info.get("hostname").is_none() {
+                let deployed_marker = self.marker("deployed_vm");
+                let echoed = [(marker_regex(&deployed_marker), true), (created_re.clone(), false)].into_iter()
+                    .filter_map(|(re, bare)| re.map(|re| (re, bare)))
+                    .find_map(|(re, bare)| {
+                        output.lines()
+                            .find_map(|l| re.find(l).map(|m| &l[m.end()..]))
+                            .and_then(|rest| echoed_hostname(rest, bare))
+                    });
+                if let Some(hostname) = echoed {
+                    vm_info["hostname"] = json!(hostname);
+                }
+            }
+            
+            // Extract price
+            if let Some(line) = output.lines().find(|l| l.contains(&self.marker("node_price"))) {
+                let parts: Vec<&str> = line.splitn(2, ':').collect();
+                if parts.len() >= 2 {
+                    let price_parts: Vec<&str> = parts[1].split('/').collect();
+                    vm_info["price"] = json!(price_parts[0].trim());
+                }
+            }
+            
+            // Extract total units
+            if let Some(line) = output.lines().find(|l| l.contains(&self.marker("total_units"))) {
+                let parts: Vec<&str> = line.splitn(2, ':').collect();
+                if parts.len() >= 2 {
+                    if let Ok(units) = parts[1].trim().parse::<i64>() {
+                        vm_info["total_units"] = json!(units);
+                    }
+                }
+            }
+            
+            // Extract locked LP
+            if let Some(lp) = parse_locked_lp(output, &self.marker("locking")) {
+                vm_info["locked_lp"] = json!(lp);
+            }
+            
+            // Extract SSH info
+            if let Some(line) = output.lines().find(|l| l.contains(&self.marker("ssh_command"))) {
+                let parts: Vec<&str> = line.split_whitespace().collect();
+                if parts.len() >= 4 {
+                    // Format: ssh -p PORT root@HOST
+                    if let Ok(port) = parts[2].parse::<i64>() {
+                        vm_info["ssh_port"] = json!(port);
+                    }
+                    
+                    let host_part = parts[3];
+                    let host = host_part.split('@').nth(1).unwrap_or("");
+                    vm_info["ssh_host"] = json!(host);
+                }
+            }
+            
+            // The SSH host may be a relay, so prefer a separately reported public IP
+            let public_ip_marker = self.marker("public_ip");
+            let public_ip = output.lines()
+                .filter_map(|l| l.find(&public_ip_marker).map(|idx| &l[idx + public_ip_marker.len()..]))
+                .find_map(|rest| rest.split_whitespace().next())
+                .map(|ip| json!(ip))
+                .unwrap_or_else(|| vm_info["ssh_host"].clone());
+            vm_info["public_ip"] = public_ip;
+            
+            // Extract UUID
+            if let Some(created_re) = &created_re {
+                // Use a simple pattern to extract UUID
+                if let Some(rest) = output.lines().find_map(|l| created_re.find(l).map(|m| &l[m.end()..])) {
+                    static CREATED_UUID_RE: LazyLock<regex::Regex> =
+                        LazyLock::new(|| regex::Regex::new(r"(?i)([0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12})").unwrap());
+                    if let Some(caps) = CREATED_UUID_RE.captures(rest) {
+                        vm_info["uuid"] = json!(normalize_worker_id(caps.get(1).unwrap().as_str()));
+                    }
+                }
+            }
+            
+            // The LP spent on the deploy can be audited through its transaction
+            let (transaction_id, receipt) = parse_transaction_ids(output);
+            if let Some(transaction_id) = transaction_id {
+                vm_info["transaction_id"] = json!(transaction_id);
+            }
+            if let Some(receipt) = receipt {
+                vm_info["receipt"] = json!(receipt);
+            }
+            
+            return Ok(Some(("vm_created", vm_info)));
+        }
+        
+        // Check for VM list output
+        if output.contains(&self.marker("table_city")) && output.contains(&self.marker("table_uuid")) {
+            let workers = self.parse_workers_table(output);
+            return Ok(Some(("vm_list", json!(workers))));
+        }
+        
+        // Check for VM update output
+        if output.contains(&self.marker("update_hardware")) || output.contains(&self.marker("update_hours")) {
+            let mut update_info = json!({
+                "success": true
+            });
+            
+            // Extract hardware modification status
+            let rejected_marker = self.marker("hardware_rejected").to_lowercase();
+            if output.contains(&self.marker("hardware_accepted")) {
+                update_info["hardware_modified"] = json!(true);
+            } else if let Some(line) = output.lines().find(|l| l.to_lowercase().contains(&rejected_marker)) {
+                // The node's explanation, if any, follows the rejection on the same line
+                let reason = line.split_once(':').map(|(_, reason)| reason).unwrap_or(line).trim();
+                update_info["success"] = json!(false);
+                update_info["hardware_modified"] = json!(false);
+                update_info["rejection_reason"] = json!(if reason.is_empty() { line.trim() } else { reason });
+            }
+            
+            // Extract hours updated
+            let hours_marker = self.marker("hours_updated");
+            if let Some(line) = output.lines().find(|l| l.contains(&hours_marker)) {
+                // The number of hours directly follows the marker text
+                if let Some(idx) = line.find(&hours_marker) {
+                    let rest = &line[idx + hours_marker.len()..];
+                    if let Some(Ok(hours)) = rest.split_whitespace().next().map(|h| h.parse::<i64>()) {
+                        update_info["hours_updated"] = json!(hours);
+                    }
+                }
+            }
+            
+            return Ok(Some(("vm_update", update_info)));
+        }
+        
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_output_is_passed_through() {
+        let extension = DeeTeeExtension::new();
+        let output = "  {\"uuid\": \"abc\", \"locked_nano\": 5}\n";
+        let (kind, value) = extension.match_cli_output(output, Path::new("out.txt")).unwrap().unwrap();
+        assert_eq!(kind, "json");
+        assert_eq!(value, json!({"uuid": "abc", "locked_nano": 5}));
+        
+        let (kind, value) = extension.match_cli_output("[1, 2]", Path::new("out.txt")).unwrap().unwrap();
+        assert_eq!((kind, value), ("json", json!([1, 2])));
+        
+        // Braces that don't parse fall back to the text heuristics
+        let result = extension.match_cli_output("{not json", Path::new("out.txt")).unwrap();
+        assert_ne!(result.map(|(kind, _)| kind), Some("json"));
+    }
+
+    #[test]
+    fn markers_can_be_overridden() {
+        let extension = DeeTeeExtension::new();
+        assert_eq!(extension.marker("vm_created"), "VM CREATED");
+        assert_eq!(extension.marker("no_such_marker"), "");
+        
+        extension.default_settings.write().unwrap()
+            .insert("markers".to_string(), json!({"vm_created": "Instance ready", "random_vm_name": "Name picked:"}));
+        assert_eq!(extension.marker("vm_created"), "Instance ready");
+        // Markers without an override keep their default
+        assert_eq!(extension.marker("deployed_vm"), "Deployed VM");
+        
+        let output = "Name picked: brave-fox\nInstance ready\n";
+        let (kind, value) = extension.match_cli_output(output, Path::new("out.txt")).unwrap().unwrap();
+        assert_eq!(kind, "vm_created");
+        assert_eq!(value["hostname"], "brave-fox");
+    }
+
+    #[test]
+    fn account_info_matches_label_aliases() {
+        let extension = DeeTeeExtension::new();
+        let output = "configuration path: /home/u/.detee\nWallet address: 5Fab\nBalance: 12 LP\n";
+        let info = extension.parse_account_info(output).unwrap();
+        assert_eq!(info["config_path"], "/home/u/.detee");
+        assert_eq!(info["wallet_public_key"], "5Fab");
+        assert_eq!(info["account_balance"], "12 LP");
+        
+        // A single label could be a coincidence
+        assert!(extension.parse_account_info("Balance: 12 LP\n").is_none());
+        
+        extension.default_settings.write().unwrap()
+            .insert("account_labels".to_string(), json!({"brain_url": ["Endpoint:"], "account_balance": "Credits:"}));
+        let info = extension.parse_account_info("Endpoint: https://brain\nCredits: 3\n").unwrap();
+        assert_eq!(info["brain_url"], "https://brain");
+        assert_eq!(info["account_balance"], "3");
+    }
+
+    #[test]
+    fn worker_rows_stay_aligned_with_the_header() {
+        let extension = DeeTeeExtension::new();
+        let table = "\
+| City | UUID | Hostname | Cores | Mem (MB) | Disk (GB) | LP/h | Time left | GPU |
+|------|------|----------|-------|----------|-----------|------|-----------|-----|
+| San Jose | CA | 7f3c-aa | brave-fox | 2 | 1,024 | 20GB | 0.5 | 3h 10m | - |
+| Berlin | 91be-bb | calm-owl | 4 | 4096 | 40 | 1.25 | 1d | h100 |
+| short | row |
+";
+        let workers = extension.parse_workers_table(table);
+        assert_eq!(workers.len(), 2);
+        
+        assert_eq!(workers[0].city, "San Jose | CA");
+        assert_eq!(workers[0].hostname, "brave-fox");
+        assert_eq!(workers[0].memory_mb, 1024);
+        assert_eq!(workers[0].disk_gb, 20);
+        assert_eq!(workers[0].time_left, "3h 10m");
+        assert_eq!(workers[0].gpu, None);
+        assert_eq!(workers[0].warnings.len(), 2);
+        
+        assert_eq!(workers[1].cores, 4);
+        assert_eq!(workers[1].lp_per_hour, 1.25);
+        assert_eq!(workers[1].gpu.as_deref(), Some("h100"));
+        assert!(workers[1].warnings.is_empty());
+    }
+
+    #[test]
+    fn durations_are_read_in_all_cli_formats() {
+        assert_eq!(parse_duration_secs("3h 20m"), Some(3 * 3600 + 20 * 60));
+        assert_eq!(parse_duration_secs("2 days 4 hours"), Some(2 * 86400 + 4 * 3600));
+        assert_eq!(parse_duration_secs("03:20:00"), Some(3 * 3600 + 20 * 60));
+        assert_eq!(parse_duration_secs("01:30"), Some(5400));
+        assert_eq!(parse_duration_secs("1.5"), Some(5400));
+        assert_eq!(parse_duration_secs("-10m"), Some(-600));
+        assert_eq!(parse_duration_secs("Expired"), Some(0));
+        assert_eq!(parse_duration_secs(""), None);
+        assert_eq!(parse_duration_secs("soon"), None);
+        assert_eq!(parse_duration_secs("1:2:3:4"), None);
+    }
+
+    #[test]
+    fn deletion_confirmed_requires_an_unnegated_marker() {
+        assert!(deletion_confirmed("VM deleted successfully", "deleted"));
+        assert!(deletion_confirmed("Refunding 3 LP\nVM 1234 Deleted.", "deleted"));
+        assert!(!deletion_confirmed("VM was not deleted", "deleted"));
+        assert!(!deletion_confirmed("The VM will be deleted shortly", "deleted"));
+        assert!(!deletion_confirmed("Found 2 undeleted snapshots", "deleted"));
+        assert!(!deletion_confirmed("", "deleted"));
+    }
+
+    #[test]
+    fn failure_markers_match_whole_words() {
+        let markers: Vec<String> = DEFAULT_FAILURE_MARKERS.iter().map(|m| m.to_string()).collect();
+        assert_eq!(marker_line("Checked 3 nodes, 0 errors\nERROR: not enough LP", &markers), Some("ERROR: not enough LP"));
+        assert_eq!(marker_line("  Permission denied (publickey)", &markers), Some("Permission denied (publickey)"));
+        assert_eq!(marker_line("Terror Bay node selected\nno errors found", &markers), None);
+        assert_eq!(marker_line("deploy failed", &["Deploy  Failed".to_string()]), Some("deploy failed"));
+    }
+
+    #[test]
+    fn markers_are_found_as_whole_words() {
+        assert!(contains_marker("Error: VM Not Found", "not found"));
+        assert!(!contains_marker("VM is not foundational", "not found"));
+        assert!(!contains_marker("anything at all", ""));
+    }
+}
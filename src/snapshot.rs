@@ -0,0 +1,227 @@
+// File: cpi_detee/src/snapshot.rs
+//! Worker snapshots: validating references, parsing listings and the snapshot actions
+
+use super::*;
+
+static SNAPSHOT_REF_RE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"^[A-Za-z0-9][A-Za-z0-9_.:-]{0,127}$").unwrap());
+
+// Check a snapshot name or ID before it becomes a CLI argument
+pub(crate) fn validate_snapshot_ref(value: &str, name: &str) -> Result<(), String> {
+    if SNAPSHOT_REF_RE.is_match(value) {
+        Ok(())
+    } else {
+        Err(format!("Parameter '{}' must start with a letter or digit and only contain letters, digits, '.', ':', '-' and '_'", name))
+    }
+}
+
+// Parse snapshot create/list output, either a table or "Key: value" blocks separated by blank
+// lines, adding the normalized "snapshot_id" and "created_at" fields to each record
+fn parse_snapshots(output: &str) -> Vec<Value> {
+    let mut records = parse_generic_table(output);
+    if records.is_empty() {
+        let mut record = serde_json::Map::new();
+        for line in output.lines().chain(std::iter::once("")) {
+            match line.split_once(':') {
+                Some((key, value)) if !key.trim().is_empty() && !value.trim().is_empty() => {
+                    let key = key.trim().to_lowercase().split_whitespace().collect::<Vec<_>>().join("_");
+                    record.entry(key).or_insert_with(|| json!(value.trim()));
+                }
+                _ if line.trim().is_empty() && !record.is_empty() => {
+                    records.push(Value::Object(std::mem::take(&mut record)));
+                }
+                _ => {}
+            }
+        }
+    }
+    
+    records.into_iter()
+        .filter_map(|mut record| {
+            let id = node_field(&record, &["snapshot_id", "id", "uuid"]).map(|v| match v {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            })?;
+            let created_at = node_field(&record, &["created", "timestamp", "date", "time"]).cloned();
+            record["snapshot_id"] = json!(id);
+            record["created_at"] = created_at.unwrap_or(Value::Null);
+            Some(record)
+        })
+        .collect()
+}
+
+impl DeeTeeExtension {
+    // The CLI's VM snapshot command, when the installed version has a `vm snapshot` subcommand
+    fn snapshot_command(&self) -> Result<String, String> {
+        let subcommands = self.run_detee_cmd("detee-cli vm --help")
+            .map(|help| self.parse_help_commands(&help))
+            .unwrap_or_default();
+        if subcommands.iter().any(|c| c == "snapshot") {
+            Ok("detee-cli vm snapshot".to_string())
+        } else {
+            Err(DeeTeeError::Validation("The installed DeeTEE CLI does not support VM snapshots".to_string()).into())
+        }
+    }
+    
+    pub(crate) fn snapshot_worker(&self, worker_id: String, snapshot_name: Option<String>) -> ActionResult {
+        let command = self.snapshot_command()?;
+        let mut command = format!("{} create {}", command, worker_id);
+        if let Some(name) = &snapshot_name {
+            command.push_str(&format!(" --name {}", name));
+        }
+        
+        let output = self.run_detee_cmd(&command)?;
+        if contains_marker(&output, &self.marker("vm_not_found")) {
+            return Err(format!("Worker with ID {} not found: {}", worker_id, output_snippet(&output, 200)));
+        }
+        let snapshot = parse_snapshots(&output).into_iter().next()
+            .ok_or_else(|| format!("Failed to find the snapshot ID in the CLI output: {}", output_snippet(&output, 200)))?;
+        
+        Ok(json!({
+            "success": true,
+            "worker_id": worker_id,
+            "snapshot_id": snapshot["snapshot_id"],
+            "snapshot_name": snapshot_name,
+            "created_at": snapshot["created_at"],
+            "snapshot": snapshot
+        }))
+    }
+    
+    pub(crate) fn list_snapshots(&self, worker_id: Option<String>) -> ActionResult {
+        let command = self.snapshot_command()?;
+        let command = match &worker_id {
+            Some(id) => format!("{} list {}", command, id),
+            None => format!("{} list", command),
+        };
+        
+        let output = self.run_detee_cmd(&command)?;
+        let snapshots = parse_snapshots(&output);
+        if snapshots.is_empty() && output.contains('|') {
+            return Err(format!("Failed to parse the snapshot listing: {}", output_snippet(&output, 200)));
+        }
+        
+        Ok(json!({
+            "success": true,
+            "worker_id": worker_id,
+            "count": snapshots.len(),
+            "snapshots": snapshots
+        }))
+    }
+    
+    pub(crate) fn restore_worker_snapshot(&self, worker_id: String, snapshot_id: String, stop_first: bool) -> ActionResult {
+        let command = self.snapshot_command()?;
+        let worker = self.find_worker(&worker_id)?;
+        let running = worker.status.as_deref()
+            .is_some_and(|status| ["running", "started", "up"].contains(&status.to_lowercase().as_str()));
+        
+        // Stopping is only attempted on request, since it interrupts whatever the VM is doing
+        let stopped = running && stop_first;
+        if stopped {
+            self.run_detee_cmd(&format!("detee-cli vm stop {}", worker_id))
+                .map_err(|e| format!("Failed to stop worker {} before restoring: {}", worker_id, e))?;
+        }
+        
+        let restored = self.run_detee_cmd(&format!("{} restore {} {}", command, worker_id, snapshot_id));
+        
+        // Bring the VM back up even when the restore failed, so it isn't left stopped
+        let restart_error = if stopped {
+            self.run_detee_cmd(&format!("detee-cli vm start {}", worker_id)).err()
+        } else {
+            None
+        };
+        
+        // A failed restore must still say that the worker was left stopped
+        let left_stopped = restart_error.as_ref()
+            .map(|e| format!("; starting worker {} again also failed, so it is left stopped: {}", worker_id, e))
+            .unwrap_or_default();
+        let output = restored.map_err(|e| format!("{}{}", e, left_stopped))?;
+        if contains_marker(&output, &self.marker("vm_must_be_stopped")) {
+            return Err(DeeTeeError::Validation(format!(
+                "Worker {} must be stopped before restoring a snapshot; pass stop_first: true to stop and restart it: {}{}",
+                worker_id, output_snippet(&output, 200), left_stopped
+            )).into());
+        }
+        if contains_marker(&output, &self.marker("vm_not_found")) {
+            return Err(format!(
+                "Worker {} or snapshot {} not found: {}{}",
+                worker_id, snapshot_id, output_snippet(&output, 200), left_stopped
+            ));
+        }
+        let confirmed = contains_marker(&output, &self.marker("snapshot_restored"));
+        
+        let mut result = json!({
+            "success": true,
+            "worker_id": worker_id,
+            "snapshot_id": snapshot_id,
+            "confirmed": confirmed,
+            "stopped_for_restore": stopped
+        });
+        if !confirmed {
+            result["output"] = json!(output_snippet(&output, 200));
+        }
+        if let Some(e) = restart_error {
+            result["restart_error"] = json!(e);
+        }
+        
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `vm list` output with one running worker, and CLI help offering snapshots
+    const RUNNING_WORKER_LIST: &str = concat!(
+        "| City | UUID | Hostname | Cores | Mem (MB) | Disk (GB) | LP/h | Time left | Status |\n",
+        "|------|------|----------|-------|----------|-----------|------|-----------|--------|\n",
+        "| Berlin | 0b7e2a4c-1f3d-4e5a-9c8b-7d6e5f4a3b2c | brave-fox | 2 | 2048 | 20 | 1 | 4h | running |\n",
+    );
+    const VM_HELP: &str = "Commands:\n  list      List VMs\n  snapshot  Manage snapshots\n\n";
+
+    fn restore_params() -> HashMap<String, Value> {
+        HashMap::from([
+            ("worker_id".to_string(), json!("0b7e2a4c-1f3d-4e5a-9c8b-7d6e5f4a3b2c")),
+            ("snapshot_id".to_string(), json!("snap-1")),
+            ("stop_first".to_string(), json!(true)),
+        ])
+    }
+
+    #[test]
+    fn restore_stops_and_restarts_a_running_worker() {
+        let (extension, runner, _dir) = crate::tests::mocked_extension();
+        runner.respond("", 0, "", "");
+        runner.respond("vm --help", 0, VM_HELP, "");
+        runner.respond("vm list", 0, RUNNING_WORKER_LIST, "");
+        runner.respond("snapshot restore", 0, "Snapshot snap-1 restored\n", "");
+        
+        let result = extension.execute_action("restore_worker_snapshot", &restore_params()).unwrap();
+        assert_eq!(result["confirmed"], true);
+        assert_eq!(result["stopped_for_restore"], true);
+        let calls: Vec<String> = runner.calls().into_iter().filter(|call| !call.contains("--help") && !call.contains("vm list")).collect();
+        assert_eq!(calls, [
+            "detee-cli vm stop 0b7e2a4c-1f3d-4e5a-9c8b-7d6e5f4a3b2c",
+            "detee-cli vm snapshot restore 0b7e2a4c-1f3d-4e5a-9c8b-7d6e5f4a3b2c snap-1",
+            "detee-cli vm start 0b7e2a4c-1f3d-4e5a-9c8b-7d6e5f4a3b2c",
+        ]);
+    }
+
+    #[test]
+    fn failed_restore_reports_a_worker_left_stopped() {
+        let (extension, runner, _dir) = crate::tests::mocked_extension();
+        runner.respond("", 0, "", "");
+        runner.respond("vm --help", 0, VM_HELP, "");
+        runner.respond("vm list", 0, RUNNING_WORKER_LIST, "");
+        runner.respond("snapshot restore", 1, "", "snapshot is corrupt");
+        runner.respond("vm start", 1, "", "node is full");
+        
+        let error = extension.execute_action("restore_worker_snapshot", &restore_params()).unwrap_err();
+        assert!(error.contains("snapshot is corrupt"), "{}", error);
+        assert!(error.contains("so it is left stopped"), "{}", error);
+        assert!(error.contains("node is full"), "{}", error);
+        
+        // A successful restart isn't mentioned
+        runner.respond("vm start", 0, "", "");
+        let error = extension.execute_action("restore_worker_snapshot", &restore_params()).unwrap_err();
+        assert!(!error.contains("left stopped"), "{}", error);
+    }
+}
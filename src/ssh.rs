@@ -0,0 +1,982 @@
+// File: cpi_detee/src/ssh.rs
+//! SSH access to workers: connection targets, remote commands, file transfers and the probes run over them
+
+use super::*;
+
+/// SSH endpoint of a worker, as reported by create_worker
+#[derive(Debug, Clone)]
+pub(crate) struct SshTarget {
+    pub(crate) host: String,
+    pub(crate) port: i64,
+    pub(crate) user: String,
+    pub(crate) jump_host: Option<String>,
+    pub(crate) connect_timeout_secs: Option<i64>,
+    pub(crate) command_timeout_secs: Option<i64>,
+}
+
+impl SshTarget {
+    // Build a target from the ssh_host, ssh_port, user, jump_host and timeout parameters
+    pub(crate) fn from_params(params: &HashMap<String, Value>) -> Result<Self, String> {
+        let host = validation::extract_string(params, "ssh_host")?.trim().to_string();
+        validate_ssh_host(&host, "ssh_host")?;
+        
+        let port = validation::extract_int_opt(params, "ssh_port")?.unwrap_or(22);
+        if !(1..=65535).contains(&port) {
+            return Err("Parameter 'ssh_port' must be between 1 and 65535".to_string());
+        }
+        
+        let user = validation::extract_string_opt(params, "user")?.unwrap_or_else(|| "root".to_string());
+        if user.is_empty() || !user.chars().all(|c| c.is_ascii_alphanumeric() || "_.-".contains(c)) {
+            return Err("Parameter 'user' must be a plain user name".to_string());
+        }
+        
+        let jump_host = validation::extract_string_opt(params, "jump_host")?
+            .map(|jump| jump.trim().to_string())
+            .filter(|jump| !jump.is_empty());
+        if let Some(jump) = &jump_host {
+            validate_jump_host(jump)?;
+        }
+        
+        let timeout = |name: &str| match validation::extract_int_opt(params, name)? {
+            Some(secs) if secs <= 0 => Err(format!("Parameter '{}' must be a positive integer", name)),
+            secs => Ok(secs),
+        };
+        let connect_timeout_secs = timeout("connect_timeout_secs")?;
+        let command_timeout_secs = timeout("command_timeout_secs")?;
+        
+        Ok(Self { host, port, user, jump_host, connect_timeout_secs, command_timeout_secs })
+    }
+    
+    // Options shared by ssh and scp, ending with the jump host if one is set
+    pub(crate) fn common_args(&self, key_path: &Path, options: &[String]) -> Vec<String> {
+        let mut args = vec!["-i".to_string(), key_path.display().to_string()];
+        args.extend_from_slice(options);
+        if let Some(secs) = self.connect_timeout_secs {
+            args.push("-o".to_string());
+            args.push(format!("ConnectTimeout={}", secs));
+        }
+        if let Some(jump) = &self.jump_host {
+            args.push("-J".to_string());
+            args.push(jump.clone());
+        }
+        args
+    }
+    
+    pub(crate) fn destination(&self) -> String {
+        format!("{}@{}", self.user, self.host)
+    }
+    
+    pub(crate) fn command_timeout(&self) -> Option<std::time::Duration> {
+        self.command_timeout_secs.map(|secs| std::time::Duration::from_secs(secs as u64))
+    }
+}
+
+// Whether ssh's error output shows the server answered but refused to let us in
+fn is_ssh_auth_failure(stderr: &str) -> bool {
+    let lower = stderr.to_lowercase();
+    ["permission denied (", "no more authentication methods", "too many authentication failures", "host key verification failed"]
+        .iter()
+        .any(|marker| lower.contains(marker))
+}
+
+// Check a path on a worker VM before it is put into a remote shell command
+fn validate_worker_path(path: &str, name: &str) -> Result<(), String> {
+    if !path.starts_with('/') {
+        return Err(format!("Parameter '{}' must be an absolute path on the VM", name));
+    }
+    if path.chars().any(|c| c.is_whitespace() || c.is_control() || "`$;&|<>'\"\\".contains(c)) {
+        return Err(format!("Parameter '{}' contains characters that are not allowed in a path", name));
+    }
+    Ok(())
+}
+
+static UNIT_NAME_RE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"^[a-zA-Z0-9@._:-]+$").unwrap());
+
+// Check a systemd unit name before it is passed to journalctl
+fn validate_unit_name(unit: &str) -> Result<(), String> {
+    if UNIT_NAME_RE.is_match(unit) {
+        Ok(())
+    } else {
+        Err(format!("Parameter 'unit' is not a valid systemd unit name: {}", unit))
+    }
+}
+
+static RSYNC_RE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"^\s*([\d,.]+[KMGT]?)\s+(\d{1,3})%\s+(\S+/s)").unwrap());
+
+static SCP_RE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"(\d{1,3})%\s+(\d+(?:\.\d+)?[KMGT]?B?)\s+(\S+/s)").unwrap());
+
+// Parse one progress update printed by rsync (`--info=progress2` or `--progress`) or by scp's
+// progress meter into its percentage, amount transferred and transfer rate
+fn parse_transfer_progress(line: &str) -> Option<(u8, String, String)> {
+    let (percent, transferred, rate) = if let Some(caps) = RSYNC_RE.captures(line) {
+        (caps[2].to_string(), caps[1].to_string(), caps[3].to_string())
+    } else {
+        let caps = SCP_RE.captures(line)?;
+        (caps[1].to_string(), caps[2].to_string(), caps[3].to_string())
+    };
+    let percent: u8 = percent.parse().ok().filter(|p| *p <= 100)?;
+    Some((percent, transferred, rate))
+}
+
+// Quote a word for the remote shell command rsync takes through -e, which it splits itself
+fn rsync_shell_word(word: &str) -> String {
+    if word.chars().any(|c| c.is_whitespace() || c == '\'' || c == '"') {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    } else {
+        word.to_string()
+    }
+}
+
+// Check SSH options, also refusing the ones that make ssh run local commands
+pub(crate) fn validate_ssh_options(value: &Value) -> Result<(), String> {
+    validate_word_list("ssh_options", value)?;
+    let words = setting_words("ssh_options", value)?;
+    let forbidden = ["proxycommand", "localcommand", "permitlocalcommand", "knownhostscommand"];
+    match words.iter().find(|word| forbidden.iter().any(|option| word.to_lowercase().starts_with(option))) {
+        Some(word) => Err(format!("Setting 'ssh_options' may not set '{}', since it runs local commands", word)),
+        None => Ok(()),
+    }
+}
+
+static SSH_HOST_RE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"^(\[[0-9a-fA-F:]+\]|[a-zA-Z0-9]([a-zA-Z0-9.-]*[a-zA-Z0-9])?)$").unwrap());
+
+// Check that a host name or IP address is safe to hand to ssh
+fn validate_ssh_host(host: &str, name: &str) -> Result<(), String> {
+    if SSH_HOST_RE.is_match(host) {
+        Ok(())
+    } else {
+        Err(format!("Parameter '{}' must be a host name or IP address", name))
+    }
+}
+
+static JUMP_HOST_RE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"^([a-zA-Z0-9_.-]+@)?(\[[0-9a-fA-F:]+\]|[a-zA-Z0-9]([a-zA-Z0-9.-]*[a-zA-Z0-9])?)(:\d{1,5})?$").unwrap());
+
+// Check a jump host list in ssh's -J format: [user@]host[:port], comma separated
+fn validate_jump_host(jump: &str) -> Result<(), String> {
+    if jump.split(',').all(|hop| JUMP_HOST_RE.is_match(hop)) {
+        Ok(())
+    } else {
+        Err(format!("Parameter 'jump_host' must be [user@]host[:port], optionally comma separated, got '{}'", jump))
+    }
+}
+
+// Script run on a worker to list its interfaces and any traffic shaping
+const NETWORK_PROBE_SCRIPT: &str = "for i in /sys/class/net/*; do n=${i##*/}; \
+    echo \"iface $n $(cat $i/speed 2>/dev/null || echo -) $(cat $i/mtu) \
+    $(cat $i/statistics/rx_bytes) $(cat $i/statistics/tx_bytes)\"; done; tc qdisc show 2>/dev/null";
+
+static SHAPING_RATE_RE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"\brate\s+(\S+)").unwrap());
+
+static SHAPING_DEV_RE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"\bdev\s+(\S+)").unwrap());
+
+// Parse the output of NETWORK_PROBE_SCRIPT into interface stats and shaping limits
+fn parse_network_probe(output: &str) -> (Vec<Value>, Vec<Value>) {
+    let mut interfaces = Vec::new();
+    let mut limits = Vec::new();
+    
+    for line in output.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.first() == Some(&"iface") && fields.len() >= 6 {
+            let number = |text: &str| text.parse::<i64>().ok();
+            interfaces.push(json!({
+                "name": fields[1],
+                // Virtual interfaces report no link speed, or -1
+                "speed_mbps": number(fields[2]).filter(|speed| *speed > 0),
+                "mtu": number(fields[3]),
+                "rx_bytes": number(fields[4]),
+                "tx_bytes": number(fields[5])
+            }));
+        } else if let Some(rate) = SHAPING_RATE_RE.captures(line) {
+            limits.push(json!({
+                "interface": SHAPING_DEV_RE.captures(line).map(|dev| dev[1].to_string()),
+                "rate": &rate[1],
+                "qdisc": fields.get(1)
+            }));
+        }
+    }
+    
+    (interfaces, limits)
+}
+
+// Script run on a worker to sample CPU counters a second apart, memory and load average
+const METRICS_PROBE_SCRIPT: &str = "head -n1 /proc/stat; sleep 1; head -n1 /proc/stat; \
+    grep -E '^(MemTotal|MemAvailable):' /proc/meminfo; cat /proc/loadavg";
+
+// Parse the output of METRICS_PROBE_SCRIPT into CPU and memory utilization
+fn parse_worker_metrics(output: &str) -> Option<Value> {
+    // Busy and total jiffies of a "cpu ..." line; idle and iowait count as idle time
+    let cpu_times = |line: &str| -> Option<(u64, u64)> {
+        let counters: Vec<u64> = line.split_whitespace().skip(1).take(8).map(|n| n.parse().ok()).collect::<Option<_>>()?;
+        if counters.len() < 4 {
+            return None;
+        }
+        let total: u64 = counters.iter().sum();
+        let idle = counters[3] + counters.get(4).copied().unwrap_or(0);
+        Some((total - idle, total))
+    };
+    let samples: Vec<(u64, u64)> = output.lines()
+        .filter(|line| line.starts_with("cpu "))
+        .filter_map(cpu_times)
+        .collect();
+    let [(busy_before, total_before), (busy_after, total_after)] = samples[..] else {
+        return None;
+    };
+    let elapsed = total_after.saturating_sub(total_before);
+    let cpu_percent = if elapsed == 0 {
+        0.0
+    } else {
+        busy_after.saturating_sub(busy_before) as f64 * 100.0 / elapsed as f64
+    };
+    
+    let meminfo_kb = |name: &str| {
+        output.lines()
+            .find_map(|line| line.strip_prefix(name))
+            .and_then(|rest| rest.split_whitespace().next())
+            .and_then(|kb| kb.parse::<u64>().ok())
+    };
+    let total_kb = meminfo_kb("MemTotal:")?;
+    let available_kb = meminfo_kb("MemAvailable:")?;
+    let used_kb = total_kb.saturating_sub(available_kb);
+    
+    // /proc/loadavg is the last line: "0.12 0.08 0.01 1/123 4567"
+    let load_average: Vec<f64> = output.lines()
+        .last()
+        .map(|line| line.split_whitespace().take(3).filter_map(|n| n.parse().ok()).collect())
+        .unwrap_or_default();
+    
+    let round = |value: f64| (value * 10.0).round() / 10.0;
+    Some(json!({
+        "cpu_percent": round(cpu_percent),
+        "memory_total_mb": total_kb / 1024,
+        "memory_used_mb": used_kb / 1024,
+        "memory_available_mb": available_kb / 1024,
+        "memory_percent": if total_kb == 0 { 0.0 } else { round(used_kb as f64 * 100.0 / total_kb as f64) },
+        "load_average": if load_average.len() == 3 { json!(load_average) } else { Value::Null }
+    }))
+}
+
+// Parse /etc/os-release's KEY=value lines, unquoting the values
+fn parse_os_release(contents: &str) -> HashMap<String, String> {
+    contents.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| {
+            let value = value.trim();
+            let unquoted = ['"', '\'']
+                .iter()
+                .find_map(|quote| value.strip_prefix(*quote).and_then(|v| v.strip_suffix(*quote)))
+                .unwrap_or(value);
+            (key.trim().to_string(), unquoted.replace("\\\"", "\"").replace("\\$", "$"))
+        })
+        .collect()
+}
+
+static BOOT_TIME_RE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"^\d{4}-\d{2}-\d{2} \d{2}:\d{2}:\d{2}$").unwrap());
+
+// Parse the output of `uptime -s; cat /proc/uptime` into the boot time and uptime seconds
+fn parse_uptime_output(output: &str) -> Option<(Option<String>, f64)> {
+    let boot_time = output.lines()
+        .map(|line| line.trim())
+        .find(|line| BOOT_TIME_RE.is_match(line))
+        .map(|line| line.to_string());
+    let uptime_secs = output.lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .find_map(|field| field.parse::<f64>().ok())?;
+    Some((boot_time, uptime_secs))
+}
+
+static KEY_FINGERPRINT_RE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"^(\d+)\s+(\S+)\s*(.*?)\s*\(([A-Za-z0-9_-]+)\)\s*$").unwrap());
+
+// Parse `ssh-keygen -l` output such as "256 SHA256:AbC... user@host (ED25519)" into the key's
+// bit length, fingerprint, comment and type
+pub(crate) fn parse_key_fingerprint(output: &str) -> Option<Value> {
+    let caps = output.lines().find_map(|line| KEY_FINGERPRINT_RE.captures(line.trim()))?;
+    Some(json!({
+        "bits": caps[1].parse::<i64>().ok(),
+        "fingerprint": &caps[2],
+        "comment": &caps[3],
+        "key_type": &caps[4]
+    }))
+}
+
+impl DeeTeeExtension {
+    // Options passed to every ssh and scp invocation: the "ssh_options" setting, or the defaults
+    fn ssh_options(&self) -> Vec<String> {
+        self.setting_words("ssh_options")
+            .unwrap_or_else(|| DEFAULT_SSH_OPTIONS.iter().map(|option| option.to_string()).collect())
+    }
+    
+    // Private key generated by setup_account, read through the container's volume mount (or from
+    // ~/.ssh in native mode)
+    pub(crate) fn worker_ssh_key(&self) -> Result<PathBuf, String> {
+        let key = self.host_ssh_dir()?.join("id_ed25519");
+        if key.is_file() {
+            Ok(key)
+        } else {
+            Err(format!("SSH private key {} not found; run setup_account first", key.display()))
+        }
+    }
+    
+    // Run ssh or scp from the host. Exit code 255 means ssh itself failed to connect.
+    fn run_ssh_tool(&self, program: &str, args: &[String], timeout: Option<std::time::Duration>) -> Result<(i32, String, String), String> {
+        let shown = format!("{} {}", program, args.join(" "));
+        self.log(&format!("Running SSH command: {}", shown));
+        self.emit(ProgressEvent::CommandRun { command: shown.clone() });
+        
+        let mut command = Command::new(program);
+        command.args(args);
+        
+        let started = std::time::Instant::now();
+        let output = self.capture_with_timeout(&mut command, &shown, timeout);
+        record_command_time(started.elapsed().as_millis() as u64);
+        let (code, stdout, stderr) = output.map_err(|e| format!("Failed to execute {}: {}", program, e))?;
+        
+        match code {
+            Some(255) | None => Err(format!("{} failed to reach the worker: {}", program, stderr.trim())),
+            Some(code) => Ok((code, stdout, stderr)),
+        }
+    }
+    
+    // Check that a worker answers over SSH, returning its current boot ID
+    fn ssh_probe(&self, target: &SshTarget) -> Result<String, String> {
+        let probe = self.run_on_worker(target, "cat /proc/sys/kernel/random/boot_id".to_string())?;
+        match probe["stdout"].as_str().map(|id| id.trim()) {
+            Some(id) if probe["exit_code"] == json!(0) && !id.is_empty() => Ok(id.to_string()),
+            _ => Err(format!("Worker answered but its boot ID could not be read: {}", probe["stderr"])),
+        }
+    }
+    
+    pub(crate) fn run_on_worker(&self, target: &SshTarget, command: String) -> ActionResult {
+        let key = self.worker_ssh_key()?;
+        self.run_on_worker_with_key(target, &key, command)
+    }
+    
+    // Run a command on a worker, logging in with the given private key
+    pub(crate) fn run_on_worker_with_key(&self, target: &SshTarget, key: &Path, command: String) -> ActionResult {
+        let mut args = target.common_args(key, &self.ssh_options());
+        args.push("-p".to_string());
+        args.push(target.port.to_string());
+        args.push(target.destination());
+        args.push(command);
+        
+        let (exit_code, stdout, stderr) = self.run_ssh_tool("ssh", &args, target.command_timeout())?;
+        
+        Ok(json!({
+            "success": exit_code == 0,
+            "exit_code": exit_code,
+            "stdout": stdout,
+            "stderr": stderr
+        }))
+    }
+    
+    pub(crate) fn copy_to_worker(
+        &self,
+        target: &SshTarget,
+        local_path: String,
+        remote_path: String,
+        backend: &str,
+        progress: bool,
+    ) -> ActionResult {
+        if !Path::new(&local_path).exists() {
+            return Err(format!("Local path {} does not exist", local_path));
+        }
+        let key = self.worker_ssh_key()?;
+        
+        let (program, args) = match backend {
+            "scp" => {
+                let mut args = target.common_args(&key, &self.ssh_options());
+                args.push("-r".to_string());
+                args.push("-P".to_string());
+                args.push(target.port.to_string());
+                args.push(local_path);
+                args.push(format!("{}:{}", target.destination(), remote_path));
+                ("scp", args)
+            },
+            "rsync" => {
+                // --partial keeps interrupted files so running the copy again resumes them
+                let mut args = vec!["-a".to_string(), "--partial".to_string()];
+                if progress {
+                    args.push("--info=progress2".to_string());
+                }
+                args.push("-e".to_string());
+                args.push(self.rsync_shell(target, &key));
+                args.push(local_path);
+                args.push(format!("{}:{}", target.destination(), remote_path));
+                ("rsync", args)
+            },
+            _ => return Err(format!("Parameter 'backend' must be scp or rsync, got '{}'", backend)),
+        };
+        
+        let (exit_code, stderr) = if progress {
+            self.run_transfer_with_progress(program, &args, target.command_timeout())?
+        } else {
+            let (exit_code, _, stderr) = self.run_ssh_tool(program, &args, target.command_timeout())?;
+            (exit_code, stderr)
+        };
+        if exit_code != 0 {
+            return Err(format!("Failed to copy to worker: {}", stderr.trim()));
+        }
+        
+        Ok(json!({
+            "success": true,
+            "backend": backend,
+            "remote_path": remote_path
+        }))
+    }
+    
+    // The ssh command rsync connects to `target` with, passed through -e
+    fn rsync_shell(&self, target: &SshTarget, key: &Path) -> String {
+        let mut ssh = vec!["ssh".to_string()];
+        ssh.extend(target.common_args(key, &self.ssh_options()));
+        ssh.push("-p".to_string());
+        ssh.push(target.port.to_string());
+        ssh.iter().map(|word| rsync_shell_word(word)).collect::<Vec<_>>().join(" ")
+    }
+    
+    // Run scp or rsync, turning the progress updates it prints into TransferProgress events.
+    // Returns the exit code and stderr.
+    fn run_transfer_with_progress(
+        &self,
+        program: &str,
+        args: &[String],
+        timeout: Option<std::time::Duration>,
+    ) -> Result<(i32, String), String> {
+        use std::io::Read;
+        use std::process::Stdio;
+        
+        let shown = format!("{} {}", program, args.join(" "));
+        self.log(&format!("Running SSH command: {}", shown));
+        self.emit(ProgressEvent::CommandRun { command: shown.clone() });
+        
+        if self.shutting_down.load(Ordering::SeqCst) {
+            return Err(format!("Failed to execute {}: the extension is shutting down", program));
+        }
+        self.audit(AuditEvent::Before { command: shown.clone() });
+        let started = std::time::Instant::now();
+        
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to execute {}: {}", program, e))?;
+        let mut stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().expect("stderr is piped");
+        
+        let id = self.next_child_id.fetch_add(1, Ordering::SeqCst);
+        self.children.lock().unwrap().insert(id, child);
+        
+        let cap = self.output_cap();
+        let stderr_reader = std::thread::spawn(move || read_capped(stderr, cap));
+        
+        // Progress updates redraw one line with carriage returns, so split on those as well
+        let mut pending = Vec::new();
+        let mut last_percent = None;
+        let mut buf = [0u8; 4096];
+        let mut cancelled = false;
+        let (_, timed_out) = self.with_child_timeout(id, timeout, || {
+            while let Ok(read) = stdout.read(&mut buf) {
+                if read == 0 {
+                    break;
+                }
+                if self.cancellation.is_cancelled() {
+                    cancelled = true;
+                    break;
+                }
+                for &byte in &buf[..read] {
+                    if byte != b'\r' && byte != b'\n' {
+                        pending.push(byte);
+                        continue;
+                    }
+                    let line = String::from_utf8_lossy(&pending).to_string();
+                    pending.clear();
+                    if let Some((percent, transferred, rate)) = parse_transfer_progress(&strip_ansi(&line)) {
+                        if last_percent != Some(percent) {
+                            last_percent = Some(percent);
+                            self.emit(ProgressEvent::TransferProgress { percent, transferred, rate });
+                        }
+                    }
+                }
+            }
+        });
+        
+        let mut child = self.children.lock().unwrap().remove(&id).expect("child is registered");
+        if cancelled {
+            let _ = child.kill();
+        }
+        let status = child.wait();
+        let stderr = stderr_reader.join().ok().and_then(|read| read.ok()).map(|(text, _)| text).unwrap_or_default();
+        
+        let duration_ms = started.elapsed().as_millis() as u64;
+        record_command_time(duration_ms);
+        self.audit(AuditEvent::After {
+            command: shown,
+            duration_ms,
+            success: matches!(&status, Ok(status) if status.success()),
+            exit_code: status.as_ref().ok().and_then(|status| status.code()),
+            error: status.as_ref().err().map(|e| e.to_string()),
+        });
+        
+        if cancelled {
+            return Err(format!("{} was cancelled", program));
+        }
+        if let (true, Some(timeout)) = (timed_out, timeout) {
+            return Err(format!("{} timed out after {} seconds and was killed", program, timeout.as_secs()));
+        }
+        let status = status.map_err(|e| format!("Failed to wait for {}: {}", program, e))?;
+        match status.code() {
+            Some(255) | None => Err(format!("{} failed to reach the worker: {}", program, stderr.trim())),
+            Some(code) => Ok((code, stderr)),
+        }
+    }
+    
+    pub(crate) fn get_worker_network(&self, target: &SshTarget) -> ActionResult {
+        let probe = match self.run_on_worker(target, NETWORK_PROBE_SCRIPT.to_string()) {
+            Ok(probe) => probe,
+            Err(e) => return Ok(json!({
+                "success": false,
+                "reachable": false,
+                "error": e
+            })),
+        };
+        
+        let (interfaces, limits) = parse_network_probe(probe["stdout"].as_str().unwrap_or_default());
+        
+        Ok(json!({
+            "success": true,
+            "reachable": true,
+            "interfaces": interfaces,
+            "bandwidth_limits": limits
+        }))
+    }
+    
+    pub(crate) fn soft_reboot_worker(&self, target: &SshTarget, wait: bool, timeout_secs: i64) -> ActionResult {
+        // The boot ID changes on every boot, which tells a finished reboot from one not started yet
+        let boot_id = self.ssh_probe(target)?;
+        
+        let command = if target.user == "root" { "reboot" } else { "sudo reboot" };
+        // The connection usually drops while the command runs, which is the expected outcome
+        if let Ok(result) = self.run_on_worker(target, command.to_string()) {
+            if result["exit_code"] != json!(0) {
+                return Err(format!("Reboot command failed: {}", result["stderr"].as_str().unwrap_or_default().trim()));
+            }
+        }
+        
+        if !wait {
+            return Ok(json!({
+                "success": true,
+                "method": "ssh",
+                "rebooting": true
+            }));
+        }
+        
+        let started = std::time::Instant::now();
+        let timeout = std::time::Duration::from_secs(timeout_secs.max(0) as u64);
+        while started.elapsed() < timeout {
+            self.sleep_cancellable(std::time::Duration::from_secs(REBOOT_POLL_INTERVAL_SECS))?;
+            if matches!(self.ssh_probe(target), Ok(id) if id != boot_id) {
+                return Ok(json!({
+                    "success": true,
+                    "method": "ssh",
+                    "back_online": true,
+                    "waited_secs": started.elapsed().as_secs()
+                }));
+            }
+        }
+        
+        Err(format!("Worker did not come back within {} seconds of the reboot", timeout_secs))
+    }
+    
+    pub(crate) fn get_worker_uptime(&self, target: &SshTarget) -> ActionResult {
+        let probe = match self.run_on_worker(target, "uptime -s; cat /proc/uptime".to_string()) {
+            Ok(probe) => probe,
+            Err(e) => return Ok(json!({
+                "success": false,
+                "reachable": false,
+                "error": e
+            })),
+        };
+        
+        let stdout = probe["stdout"].as_str().unwrap_or_default();
+        let (boot_time, uptime_secs) = parse_uptime_output(stdout)
+            .ok_or_else(|| format!("Failed to parse uptime output: {}", output_snippet(stdout, 200)))?;
+        
+        Ok(json!({
+            "success": true,
+            "reachable": true,
+            "boot_time": boot_time,
+            "uptime_secs": uptime_secs as i64
+        }))
+    }
+    
+    pub(crate) fn follow_worker_logs(
+        &self,
+        target: &SshTarget,
+        path: Option<String>,
+        unit: Option<String>,
+        max_lines: Option<i64>,
+        timeout_secs: Option<i64>,
+    ) -> ActionResult {
+        use std::io::BufRead;
+        use std::process::Stdio;
+        use std::sync::mpsc::RecvTimeoutError;
+        use std::time::{Duration, Instant};
+        
+        let remote = match (&path, &unit) {
+            (Some(_), Some(_)) => return Err("Pass either 'path' or 'unit', not both".to_string()),
+            (Some(path), None) => {
+                validate_worker_path(path, "path")?;
+                format!("tail -n 0 -F {}", path)
+            },
+            (None, Some(unit)) => {
+                validate_unit_name(unit)?;
+                format!("journalctl -f -n 0 --no-pager -u {}", unit)
+            },
+            (None, None) => "journalctl -f -n 0 --no-pager".to_string(),
+        };
+        let key = self.worker_ssh_key()?;
+        
+        let mut args = target.common_args(&key, &self.ssh_options());
+        args.push("-p".to_string());
+        args.push(target.port.to_string());
+        args.push(target.destination());
+        args.push(remote);
+        
+        let shown = format!("ssh {}", args.join(" "));
+        self.log(&format!("Following logs: {}", shown));
+        self.emit(ProgressEvent::CommandRun { command: shown.clone() });
+        
+        if self.shutting_down.load(Ordering::SeqCst) {
+            return Err("Failed to execute ssh: the extension is shutting down".to_string());
+        }
+        self.audit(AuditEvent::Before { command: shown.clone() });
+        let started = Instant::now();
+        
+        let mut child = Command::new("ssh")
+            .args(&args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to execute ssh: {}", e))?;
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().expect("stderr is piped");
+        
+        let id = self.next_child_id.fetch_add(1, Ordering::SeqCst);
+        self.children.lock().unwrap().insert(id, child);
+        
+        // Lines arrive on a reader thread so cancellation can be checked between them
+        let (lines, received) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            for line in std::io::BufReader::new(stdout).lines().map_while(Result::ok) {
+                if lines.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let cap = self.output_cap();
+        let stderr_reader = std::thread::spawn(move || read_capped(stderr, cap));
+        
+        let deadline = timeout_secs.map(|secs| started + Duration::from_secs(secs.max(0) as u64));
+        let keep_ansi = self.setting_bool("keep_ansi");
+        let mut forwarded: i64 = 0;
+        let mut last_lines = std::collections::VecDeque::new();
+        let stopped_by = loop {
+            if self.cancellation.is_cancelled() {
+                break "cancelled";
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                break "timeout";
+            }
+            match received.recv_timeout(Duration::from_millis(CANCEL_CHECK_INTERVAL_MS)) {
+                Ok(line) => {
+                    let line = if keep_ansi { line } else { strip_ansi(&line) };
+                    self.emit(ProgressEvent::LogLine { line: line.clone() });
+                    forwarded += 1;
+                    if last_lines.len() == FOLLOW_TAIL_LINES {
+                        last_lines.pop_front();
+                    }
+                    last_lines.push_back(line);
+                    if max_lines.is_some_and(|max| forwarded >= max) {
+                        break "max_lines";
+                    }
+                },
+                Err(RecvTimeoutError::Timeout) => {},
+                Err(RecvTimeoutError::Disconnected) => break "ended",
+            }
+        };
+        
+        let mut child = self.children.lock().unwrap().remove(&id).expect("child is registered");
+        if stopped_by != "ended" {
+            let _ = child.kill();
+        }
+        let status = child.wait();
+        let stderr = stderr_reader.join().ok().and_then(|read| read.ok()).map(|(text, _)| text).unwrap_or_default();
+        
+        let duration_ms = started.elapsed().as_millis() as u64;
+        record_command_time(duration_ms);
+        self.audit(AuditEvent::After {
+            command: shown,
+            duration_ms,
+            success: stopped_by != "ended" || matches!(&status, Ok(status) if status.success()),
+            exit_code: status.as_ref().ok().and_then(|status| status.code()),
+            error: status.as_ref().err().map(|e| e.to_string()),
+        });
+        
+        let status = status.map_err(|e| format!("Failed to wait for ssh: {}", e))?;
+        if stopped_by == "ended" && status.code() == Some(255) {
+            return Err(format!("ssh failed to reach the worker: {}", stderr.trim()));
+        }
+        
+        Ok(json!({
+            "success": true,
+            "stopped_by": stopped_by,
+            "lines_forwarded": forwarded,
+            "last_lines": last_lines,
+            "exit_code": if stopped_by == "ended" { json!(status.code()) } else { Value::Null },
+            "stderr": stderr
+        }))
+    }
+    
+    pub(crate) fn test_worker_ssh_auth(&self, target: &SshTarget) -> ActionResult {
+        // A no-op command separates "the VM answers" from "our key is accepted"
+        match self.run_on_worker(target, "true".to_string()) {
+            Ok(probe) => Ok(json!({
+                "success": true,
+                "reachable": true,
+                "authenticated": probe["exit_code"] == json!(0),
+                "error": if probe["exit_code"] == json!(0) { Value::Null } else { probe["stderr"].clone() }
+            })),
+            Err(e) => Ok(json!({
+                "success": true,
+                "reachable": is_ssh_auth_failure(&e),
+                "authenticated": false,
+                "error": e
+            })),
+        }
+    }
+    
+    pub(crate) fn get_worker_metrics(&self, target: &SshTarget) -> ActionResult {
+        let probe = match self.run_on_worker(target, METRICS_PROBE_SCRIPT.to_string()) {
+            Ok(probe) => probe,
+            Err(e) => return Ok(json!({
+                "success": false,
+                "reachable": false,
+                "error": e
+            })),
+        };
+        
+        let stdout = probe["stdout"].as_str().unwrap_or_default();
+        let mut metrics = parse_worker_metrics(stdout)
+            .ok_or_else(|| format!("Failed to parse worker metrics: {}", output_snippet(stdout, 200)))?;
+        metrics["success"] = json!(true);
+        metrics["reachable"] = json!(true);
+        
+        Ok(metrics)
+    }
+    
+    pub(crate) fn get_worker_os(&self, target: &SshTarget) -> ActionResult {
+        let probe = match self.run_on_worker(target, "cat /etc/os-release".to_string()) {
+            Ok(probe) => probe,
+            Err(e) => return Ok(json!({
+                "success": false,
+                "reachable": false,
+                "error": e
+            })),
+        };
+        
+        if probe["exit_code"] != json!(0) {
+            return Ok(json!({
+                "success": false,
+                "reachable": true,
+                "error": format!("Failed to read /etc/os-release: {}", probe["stderr"].as_str().unwrap_or_default().trim())
+            }));
+        }
+        
+        let release = parse_os_release(probe["stdout"].as_str().unwrap_or_default());
+        Ok(json!({
+            "success": true,
+            "reachable": true,
+            "os_id": release.get("ID"),
+            "os_version": release.get("VERSION_ID"),
+            "pretty_name": release.get("PRETTY_NAME")
+        }))
+    }
+    
+    pub(crate) fn migrate_worker(
+        &self,
+        source: &SshTarget,
+        source_id: Option<String>,
+        spec: &WorkerSpec,
+        path: String,
+        delete_old: bool,
+        timeout_secs: i64,
+    ) -> ActionResult {
+        validate_worker_path(&path, "path")?;
+        if Path::new(&path).file_name().is_none() {
+            return Err(format!("Parameter 'path' must name a file or directory, got {}", path));
+        }
+        if delete_old && source_id.is_none() {
+            return Err("Parameter 'worker_id' is required to delete the old worker".to_string());
+        }
+        // Fail before deploying anything if the source can't be read from
+        self.ssh_probe(source)?;
+        
+        let created = self.create_worker(spec, false)?;
+        let new_id = created["uuid"].as_str()
+            .ok_or_else(|| "The new worker's UUID could not be read from the deploy output".to_string())?
+            .to_string();
+        
+        // Anything failing from here on removes the new worker again
+        let copied = self.copy_between_workers(source, &created, &path, timeout_secs);
+        if let Err(e) = copied {
+            return Err(match self.delete_worker(new_id.clone()) {
+                Ok(_) => format!("Migration failed, deleted the new worker {}: {}", new_id, e),
+                Err(delete_error) => format!(
+                    "Migration failed and deleting the new worker {} also failed ({}): {}",
+                    new_id, delete_error, e
+                ),
+            });
+        }
+        
+        let mut result = json!({
+            "success": true,
+            "new_worker": created,
+            "path": path,
+            "old_deleted": false
+        });
+        
+        // The data is safe on the new worker, so a failed delete is only reported
+        if let (true, Some(source_id)) = (delete_old, source_id) {
+            match self.delete_worker(source_id) {
+                Ok(_) => result["old_deleted"] = json!(true),
+                Err(e) => result["delete_error"] = json!(e),
+            }
+        }
+        
+        Ok(result)
+    }
+    
+    // Wait for a newly created worker to answer over SSH, then copy `path` to it from `source`
+    fn copy_between_workers(&self, source: &SshTarget, created: &Value, path: &str, timeout_secs: i64) -> Result<(), String> {
+        let target = SshTarget {
+            host: created["ssh_host"].as_str()
+                .filter(|host| !host.is_empty())
+                .ok_or_else(|| "The new worker's SSH host could not be read from the deploy output".to_string())?
+                .to_string(),
+            port: created["ssh_port"].as_i64().unwrap_or(22),
+            user: source.user.clone(),
+            jump_host: source.jump_host.clone(),
+            connect_timeout_secs: source.connect_timeout_secs,
+            command_timeout_secs: source.command_timeout_secs,
+        };
+        
+        let started = std::time::Instant::now();
+        let timeout = std::time::Duration::from_secs(timeout_secs.max(0) as u64);
+        while let Err(e) = self.ssh_probe(&target) {
+            if started.elapsed() >= timeout {
+                return Err(format!("New worker did not answer over SSH within {} seconds: {}", timeout_secs, e));
+            }
+            self.sleep_cancellable(std::time::Duration::from_secs(REBOOT_POLL_INTERVAL_SECS))?;
+        }
+        
+        // The data is staged on this host, so the workers never need each other's keys, and
+        // both legs go through rsync like copy_to_worker's rsync backend
+        let name = Path::new(path).file_name()
+            .ok_or_else(|| format!("Parameter 'path' must name a file or directory, got {}", path))?;
+        let parent = Path::new(path).parent().map(|p| p.display().to_string()).unwrap_or_else(|| "/".to_string());
+        let staging = tempfile::tempdir().map_err(|e| format!("Failed to create a staging directory: {}", e))?;
+        
+        let key = self.worker_ssh_key()?;
+        let args = vec![
+            "-a".to_string(),
+            "--partial".to_string(),
+            "-e".to_string(),
+            self.rsync_shell(source, &key),
+            format!("{}:{}", source.destination(), path),
+            format!("{}/", staging.path().display()),
+        ];
+        let (exit_code, _, stderr) = self.run_ssh_tool("rsync", &args, source.command_timeout())?;
+        if exit_code != 0 {
+            return Err(format!("Failed to copy {} from the old worker: {}", path, stderr.trim()));
+        }
+        
+        let staged = staging.path().join(name).display().to_string();
+        self.copy_to_worker(&target, staged, format!("{}/", parent.trim_end_matches('/')), "rsync", false)
+            .map_err(|e| format!("Failed to copy {} to the new worker: {}", path, e))?;
+        Ok(())
+    }
+    
+    // Listings don't include SSH endpoints, so use the given ones or the VM's public IP
+    pub(crate) fn worker_ssh_endpoint(&self, worker: &WorkerInfo, endpoints: &serde_json::Map<String, Value>) -> Option<(String, u16)> {
+        match endpoints.get(&worker.uuid) {
+            Some(endpoint) => endpoint["ssh_host"].as_str()
+                .map(|host| (host.to_string(), endpoint["ssh_port"].as_u64().unwrap_or(22) as u16)),
+            None => self.get_worker(worker.uuid.clone()).ok()
+                .and_then(|details| details["vm"]["public_ip"].as_str().map(|ip| (ip.to_string(), 22))),
+        }
+    }
+    
+    // SSH target of an action's parameters, with the ssh_connect_timeout_secs and
+    // ssh_command_timeout_secs settings filling in timeouts the caller didn't pass
+    pub(crate) fn ssh_target(&self, params: &HashMap<String, Value>) -> Result<SshTarget, String> {
+        let mut target = SshTarget::from_params(params)?;
+        target.connect_timeout_secs = target.connect_timeout_secs
+            .or_else(|| self.setting_i64("ssh_connect_timeout_secs").filter(|&secs| secs > 0));
+        target.command_timeout_secs = target.command_timeout_secs
+            .or_else(|| self.setting_i64("ssh_command_timeout_secs").filter(|&secs| secs > 0));
+        Ok(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connect_timeout_is_passed_to_ssh() {
+        let (extension, runner, dir) = crate::tests::mocked_extension();
+        std::fs::write(dir.path().join("id_ed25519"), "key").unwrap();
+        runner.respond("ssh", 0, "ok\n", "");
+        
+        let params = HashMap::from([
+            ("ssh_host".to_string(), json!("vm.example")),
+            ("command".to_string(), json!("uptime")),
+            ("connect_timeout_secs".to_string(), json!(7)),
+        ]);
+        extension.execute_action("run_on_worker", &params).unwrap();
+        assert!(runner.calls()[0].contains(" -o ConnectTimeout=7 "), "{}", runner.calls()[0]);
+        
+        // Without the parameter the setting applies, and without either no timeout is set
+        let params = HashMap::from([("ssh_host".to_string(), json!("vm.example")), ("command".to_string(), json!("uptime"))]);
+        extension.execute_action("run_on_worker", &params).unwrap();
+        assert!(!runner.calls()[1].contains("ConnectTimeout"), "{}", runner.calls()[1]);
+        extension.default_settings.write().unwrap().insert("ssh_connect_timeout_secs".to_string(), json!(4));
+        extension.execute_action("run_on_worker", &params).unwrap();
+        assert!(runner.calls()[2].contains(" -o ConnectTimeout=4 "), "{}", runner.calls()[2]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn hung_commands_are_killed_after_the_command_timeout() {
+        let extension = DeeTeeExtension::new();
+        let params = HashMap::from([("ssh_host".to_string(), json!("vm.example")), ("command_timeout_secs".to_string(), json!(1))]);
+        let target = extension.ssh_target(&params).unwrap();
+        
+        let started = std::time::Instant::now();
+        let error = extension.run_ssh_tool("sleep", &["30".to_string()], target.command_timeout()).unwrap_err();
+        assert!(error.contains("timed out after 1 seconds and was killed"), "{}", error);
+        assert!(started.elapsed() < std::time::Duration::from_secs(10));
+    }
+}